@@ -0,0 +1,1813 @@
+use super::super::config::models::Config;
+use super::super::money::Amount;
+use super::super::transactions::models::{Columns, Transaction, TransactionError, TransactionType};
+use super::models::{Client, LockReason};
+use std::collections::hash_map::Entry;
+#[cfg(debug_assertions)]
+use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Balances of all the clients, updated one transaction at a time.
+/// The clients are kept in a [`HashMap`] by ID, so applying a transaction doesn't depend on the number of clients.
+///
+/// Disputes look up the amount of the deposit they reference, so the ledger keeps the amount of every
+/// deposit applied so far. That is the only part that grows with the input besides the clients,
+/// and the disputes that wait for a deposit not applied yet.
+/// The deposits are found by tx ID or by `ref`, as told by the `dispute_key` of the [`Config`].
+/// The held amount of each open dispute is kept by client, for the `held_breakdown` output.
+/// # Examples
+/// ```
+/// use accounts_cli::clients::ledger::ClientLedger;
+/// use accounts_cli::transactions::models::{Transaction, TransactionType};
+///
+/// let mut ledger: ClientLedger = ClientLedger::new();
+/// ledger.apply(&Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap()));
+/// assert_eq!(ledger.into_clients().len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct ClientLedger {
+    pub(super) clients: HashMap<u32, Client>,
+    /// Client IDs in the order they were first seen.
+    pub(super) order: Vec<u32>,
+    /// Client, tx ID and amount of each applied deposit by its dispute key.
+    deposits: HashMap<String, (u32, u32, Amount)>,
+    /// Client, tx ID and amount of each applied withdrawal by its dispute key.
+    withdrawals: HashMap<String, (u32, u32, Amount)>,
+    /// Held amount of the open disputes of each client, by the tx ID of the deposit.
+    pub(super) held: HashMap<u32, BTreeMap<u32, Amount>>,
+    /// State of each dispute by the client and the tx ID of the disputed transaction.
+    disputes: HashMap<(u32, u32), DisputeState>,
+    /// Clients as they were seeded with [`ClientLedger::seed`], for `changed_only`.
+    pub(super) opening: HashMap<u32, Client>,
+    /// Currency of each client, the first one found in its transactions.
+    pub(super) currencies: HashMap<u32, String>,
+    /// Disputes, resolves and chargebacks whose deposit was not found, kept with `report_unmatched`.
+    unmatched: Vec<Unmatched>,
+    /// Disputes, resolves and chargebacks waiting for the transaction they reference, by its dispute key.
+    pending: HashMap<String, Vec<Transaction>>,
+    /// Ledgers of the balances in each currency with `split_currencies`, by the code of the currency.
+    /// This ledger keeps the balances of the transactions without a currency.
+    pub(super) split: BTreeMap<String, ClientLedger>,
+    report: ProcessReport,
+    config: Config,
+    /// IDs of the clients of the applied transactions and of the seeded ones, checked against the
+    /// clients by [`ClientLedger::into_clients`] in debug builds.
+    #[cfg(debug_assertions)]
+    seen: HashSet<u32>,
+}
+
+/// What applying a transaction did, see [`ClientLedger::apply`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The balances or the lock of a client changed.
+    Applied,
+    /// Nothing changed, like a withdrawal without the funds, a deposit to a locked client,
+    /// a dispute of an unknown deposit or a repeated one.
+    Rejected,
+}
+
+/// What applying the transactions skipped or rejected, see [`ClientLedger::report`].
+/// # Examples
+/// ```
+/// # use accounts_cli::clients::ledger::ProcessReport;
+/// let report: ProcessReport = ProcessReport {
+///     skipped_locked: [(1, 2), (3, 1)].into(),
+///     ..ProcessReport::default()
+/// };
+/// assert_eq!(report.skipped(), 3);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProcessReport {
+    /// Number of transactions ignored because their client was locked, by client ID.
+    pub skipped_locked: BTreeMap<u32, u32>,
+    /// Withdrawals rejected because the client didn't have the funds, in the order they were applied.
+    pub rejected_withdrawals: Vec<RejectedWithdrawal>,
+}
+
+/// A withdrawal for more than the client had available, so it changed nothing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RejectedWithdrawal {
+    pub client: u32,
+    pub tx: u32,
+    /// The amount of the withdrawal.
+    pub requested: Amount,
+    /// The available funds of the client when the withdrawal was rejected.
+    pub available: Amount,
+}
+
+impl ProcessReport {
+    /// Returns how many transactions were skipped for locked clients, of all the clients.
+    pub fn skipped(&self) -> u32 {
+        self.skipped_locked.values().sum()
+    }
+}
+
+/// Where the dispute of a transaction is, see [`ClientLedger::dispute_state`].
+/// Only a [`DisputeState::Disputed`] transaction can be resolved or charged back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DisputeState {
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+/// A dispute, resolve or chargeback whose deposit was not found, so it changed nothing.
+/// # Examples
+/// ```
+/// # use accounts_cli::clients::ledger::Unmatched;
+/// # use accounts_cli::transactions::models::TransactionType;
+/// let unmatched: Unmatched = Unmatched {
+///     tx_type: TransactionType::Dispute,
+///     client: 1,
+///     tx: 7,
+///     reference: Some("5".to_string()),
+/// };
+/// assert_eq!(unmatched.to_string(), "dispute tx 7 for client 1 references 5, not found");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmatched {
+    pub tx_type: TransactionType,
+    pub client: u32,
+    pub tx: u32,
+    /// The dispute key it references, [`None`] if the row has no `ref` with the `ref` key.
+    pub reference: Option<String>,
+}
+
+impl fmt::Display for Unmatched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tx {} for client {} references ",
+            self.tx_type, self.tx, self.client
+        )?;
+        match &self.reference {
+            Some(reference) => write!(f, "{}, not found", reference),
+            None => write!(f, "nothing, the ref is missing"),
+        }
+    }
+}
+
+impl ClientLedger {
+    /// Create a new empty ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new empty ledger that applies the transactions as told by the [`Config`].
+    pub fn with_config(config: &Config) -> Self {
+        Self {
+            config: config.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Start the ledger from the opening balances of the clients, like the output of an earlier run.
+    /// The seeded clients keep their place in the order, before the ones first seen in the transactions.
+    /// A client seeded twice has the balances of the last one, [`Client::clients_from_reader`] rejects a repeated ID.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::ledger::ClientLedger;
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::TransactionType;
+    /// let mut ledger: ClientLedger = ClientLedger::new();
+    /// ledger.seed([Client::new(1).new_transaction(TransactionType::Deposit, "1.0".parse().unwrap())]);
+    /// assert_eq!(ledger.get(1).unwrap().total(), "1.0".parse().unwrap());
+    /// ```
+    pub fn seed(&mut self, clients: impl IntoIterator<Item = Client>) {
+        for client in clients {
+            if self.clients.insert(client.client, client).is_none() {
+                self.order.push(client.client);
+            }
+            self.opening.insert(client.client, client);
+            #[cfg(debug_assertions)]
+            self.seen.insert(client.client);
+        }
+    }
+
+    /// Apply the transaction to its client, creating the client when the ID is new.
+    /// Every transaction is counted in the [`TypeCounts`](super::models::TypeCounts) of its client, even if it's ignored.
+    ///
+    /// A resolve with an amount lower than the held amount of its dispute only frees that part,
+    /// the rest stays held until another resolve or a chargeback. A resolve with an amount higher
+    /// than the held amount of its dispute is ignored.
+    ///
+    /// A chargeback locks the client: later deposits and withdrawals are ignored, and so are
+    /// disputes of transactions that were never applied. Disputes, resolves and chargebacks of the
+    /// deposits applied before the lock are still settled, so their held funds are not kept forever.
+    ///
+    /// A dispute of a deposit that is already disputed is ignored, so a dispute row repeated by an
+    /// upstream retry holds the funds only once. After a resolve or a chargeback the deposit can be disputed again.
+    ///
+    /// A resolve or a chargeback only settles a transaction under dispute, see [`DisputeState`], so one
+    /// without a dispute, or repeated after its dispute was settled, changes nothing.
+    ///
+    /// A dispute holds the amount of the transaction it references, never the amount of its own row.
+    /// A dispute, resolve or chargeback whose transaction is not found yet waits for it, and is applied
+    /// right after it in the order they came, like a dispute sorted before its deposit by the `tx` order.
+    /// Until then, or if the transaction never comes, it changes nothing, even if it creates its client.
+    ///
+    /// With `max_dispute_ratio` a dispute row with an amount higher than the disputed deposit times
+    /// the ratio is ignored. A dispute without amount is always applied, and it holds the amount of the deposit.
+    ///
+    /// A withdrawal can be disputed too, the client claims it was not made. The dispute holds the claimed
+    /// amount, so held and total go up while available stays as it was after the withdrawal. A resolve
+    /// rejects the claim: the held amount is dropped and the withdrawal stands, leaving the balances as
+    /// they were before the dispute. A chargeback accepts it: the amount goes back to available and the
+    /// client is locked. Resolves of withdrawal disputes always settle the whole amount.
+    ///
+    /// The chargeback that locks a client is recorded as its [`Client::locked_reason`] and [`Client::locked_at_tx`].
+    ///
+    /// With `clamp_negative_total` a total that goes below zero is floored at zero, see [`Client::clamp_total`].
+    ///
+    /// With `split_currencies` the transactions of each currency are applied to their own balances, the
+    /// ones of its [`ClientLedger::ledger_for`], so a client has a balance in each of its currencies. A dispute,
+    /// resolve or chargeback only finds a transaction of its currency, or of any currency if its row has none.
+    ///
+    /// Returns the [`Outcome`], if the transaction changed the balances of its clients or was rejected.
+    pub fn apply(&mut self, transaction: &Transaction) -> Outcome {
+        if let Some(code) = self.split_currency(transaction) {
+            let config: Config = Config {
+                split_currencies: false,
+                ..self.config.clone()
+            };
+            let ledger: &mut ClientLedger = self
+                .split
+                .entry(code)
+                .or_insert_with(|| ClientLedger::with_config(&config));
+            let outcome: Outcome = ledger.apply(transaction);
+
+            //The report is of the whole run, whatever the currency
+            let report: ProcessReport = std::mem::take(&mut ledger.report);
+            for (client, skipped) in report.skipped_locked {
+                *self.report.skipped_locked.entry(client).or_default() += skipped;
+            }
+            self.report
+                .rejected_withdrawals
+                .extend(report.rejected_withdrawals);
+            return outcome;
+        }
+
+        //Only a transfer creates its counterparty, on other rows it's ignored
+        #[cfg(debug_assertions)]
+        self.seen.extend(
+            std::iter::once(transaction.client).chain(
+                transaction
+                    .counterparty
+                    .filter(|_| transaction.tx_type == TransactionType::Transfer),
+            ),
+        );
+
+        let ids: [u32; 2] = [
+            transaction.client,
+            transaction.counterparty.unwrap_or(transaction.client),
+        ];
+        let before: [Client; 2] =
+            ids.map(|id| self.clients.get(&id).copied().unwrap_or(Client::new(id)));
+
+        if let Some(currency) = &transaction.currency {
+            self.currencies
+                .entry(transaction.client)
+                .or_insert_with(|| currency.clone());
+        }
+
+        let was_locked: bool = self
+            .clients
+            .get(&transaction.client)
+            .is_some_and(|c| c.locked);
+        self.apply_to_client(transaction);
+
+        //Only a chargeback locks a client
+        if let Some(client) = self.clients.get_mut(&transaction.client) {
+            if client.locked && !was_locked {
+                client.locked_reason = Some(LockReason::Chargeback);
+                client.locked_at_tx = Some(transaction.tx);
+            }
+        }
+
+        if self.config.clamp_negative_total {
+            if let Some(client) = self.clients.get_mut(&transaction.client) {
+                *client = client.clamp_total();
+            }
+        }
+
+        let changed: bool = ids.iter().zip(&before).any(|(id, before)| {
+            self.clients
+                .get(id)
+                .is_some_and(|after| !after.same_balance(before))
+        });
+        if changed {
+            Outcome::Applied
+        } else {
+            Outcome::Rejected
+        }
+    }
+
+    fn apply_to_client(&mut self, transaction: &Transaction) {
+        if transaction.tx_type == TransactionType::Transfer {
+            return self.apply_transfer(transaction);
+        }
+
+        //If the user don't exists, create a new one, the transaction is made the same way
+        if let Entry::Vacant(entry) = self.clients.entry(transaction.client) {
+            self.order.push(transaction.client);
+            entry.insert(Client::new(transaction.client));
+        }
+        if let Some(client) = self.clients.get_mut(&transaction.client) {
+            client.counts.add(transaction.tx_type);
+        }
+
+        self.apply_counted(transaction);
+    }
+
+    /// Apply the transaction to its client, already created and with the transaction counted.
+    fn apply_counted(&mut self, transaction: &Transaction) {
+        let client_id: u32 = transaction.client;
+        let key: Option<String> = transaction.dispute_key(self.config.dispute_key);
+        let deposit: Option<(u32, u32, Amount)> =
+            key.as_ref().and_then(|k| self.deposits.get(k)).copied();
+        let withdrawal: Option<(u32, u32, Amount)> = match deposit {
+            Some(_) => None,
+            None => key.as_ref().and_then(|k| self.withdrawals.get(k)).copied(),
+        };
+
+        //A client can only dispute its own transactions
+        if let (true, Some((owner, tx, _))) = (transaction.is_dispute(), deposit.or(withdrawal)) {
+            if owner != client_id {
+                log::debug!(
+                    "Rejected {} tx {} of client {}, tx {} is of client {}",
+                    transaction.tx_type,
+                    transaction.tx,
+                    client_id,
+                    tx,
+                    owner
+                );
+                return;
+            }
+        }
+        let deposit: Option<(u32, Amount)> = deposit.map(|(_, tx, amount)| (tx, amount));
+        let withdrawal: Option<(u32, Amount)> = withdrawal.map(|(_, tx, amount)| (tx, amount));
+        let available: Amount = self
+            .clients
+            .get(&client_id)
+            .map_or(Amount::ZERO, |c| c.available);
+
+        if transaction.is_dispute() && deposit.is_none() && withdrawal.is_none() {
+            let unmatched: Unmatched = Unmatched {
+                tx_type: transaction.tx_type,
+                client: client_id,
+                tx: transaction.tx,
+                reference: key.clone(),
+            };
+            log::debug!("{}", unmatched);
+            if self.config.report_unmatched {
+                self.unmatched.push(unmatched);
+            }
+        }
+
+        let client: &mut Client = match self.clients.get_mut(&client_id) {
+            Some(x) => x,
+            None => return,
+        };
+
+        //A locked client only settles the disputes of the transactions applied before the lock
+        if client.locked
+            && !(transaction.is_dispute() && (deposit.is_some() || withdrawal.is_some()))
+        {
+            log::debug!(
+                "Skipping {} tx {} of locked client {}",
+                transaction.tx_type,
+                transaction.tx,
+                client_id
+            );
+            *self.report.skipped_locked.entry(client_id).or_default() += 1;
+            return;
+        }
+
+        if let (true, Some((tx, amount))) = (transaction.is_dispute(), withdrawal) {
+            let held: &mut BTreeMap<u32, Amount> = self.held.entry(client_id).or_default();
+            let state: Option<DisputeState> = self.disputes.get(&(client_id, tx)).copied();
+
+            match (transaction.tx_type, state) {
+                (TransactionType::Dispute, Some(DisputeState::Disputed)) => {}
+                (TransactionType::Dispute, _) => {
+                    *client = client.dispute_withdrawal(TransactionType::Dispute, amount);
+                    held.insert(tx, amount);
+                    self.disputes
+                        .insert((client_id, tx), DisputeState::Disputed);
+                }
+                (
+                    tx_type @ (TransactionType::Resolve | TransactionType::Chargeback),
+                    Some(DisputeState::Disputed),
+                ) => {
+                    *client =
+                        client.dispute_withdrawal(tx_type, held.remove(&tx).unwrap_or(amount));
+                    self.disputes.insert((client_id, tx), settled(tx_type));
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        //If the transaction is a dispute, the previos amount need to be found
+        if transaction.is_dispute() {
+            if let Some((tx, amount)) = deposit {
+                let over_disputed: bool = self
+                    .config
+                    .max_dispute_ratio
+                    .is_some_and(|ratio| exceeds_ratio(transaction.amount, amount, ratio));
+
+                //If the previos tx exists, make the transaction.
+                let held: &mut BTreeMap<u32, Amount> = self.held.entry(client_id).or_default();
+                let open: Amount = held.get(&tx).copied().unwrap_or(amount);
+                let state: Option<DisputeState> = self.disputes.get(&(client_id, tx)).copied();
+
+                match (transaction.tx_type, state) {
+                    //A dispute for more than allowed by the deposit is rejected
+                    (TransactionType::Dispute, _) if over_disputed => {}
+                    //The deposit is already disputed, like a dispute row repeated by a retry
+                    (TransactionType::Dispute, Some(DisputeState::Disputed)) => {}
+                    (TransactionType::Dispute, _) => {
+                        *client = client.new_transaction(TransactionType::Dispute, amount);
+                        held.insert(tx, amount);
+                        self.disputes
+                            .insert((client_id, tx), DisputeState::Disputed);
+                    }
+                    //A partial resolve can't free more than the dispute holds
+                    (TransactionType::Resolve, Some(DisputeState::Disputed))
+                        if transaction.amount > open => {}
+                    (TransactionType::Resolve, Some(DisputeState::Disputed))
+                        if transaction.amount > Amount::ZERO && transaction.amount < open =>
+                    {
+                        *client = client.resolve_part(transaction.amount);
+                        held.insert(tx, open - transaction.amount);
+                    }
+                    //Resolves and chargebacks settle what is left of the dispute
+                    (
+                        tx_type @ (TransactionType::Resolve | TransactionType::Chargeback),
+                        Some(DisputeState::Disputed),
+                    ) => {
+                        *client = client.new_transaction(tx_type, open);
+                        held.remove(&tx);
+                        self.disputes.insert((client_id, tx), settled(tx_type));
+                    }
+                    //A resolve or chargeback without an open dispute changes nothing
+                    _ => {}
+                }
+            }
+            //A dispute of a transaction that was not found waits for it, see ClientLedger::replay
+            if let (None, Some(key)) = (deposit, key) {
+                self.pending
+                    .entry(key)
+                    .or_default()
+                    .push(transaction.clone());
+            }
+            return;
+        }
+
+        //If the transaction is not a dispute, the amount of tx is used
+        *client = match transaction.tx_type {
+            //A withdrawal without the funds is recorded for the report
+            TransactionType::Withdrawal => {
+                client.withdraw(transaction.amount).unwrap_or_else(|| {
+                    log::debug!(
+                        "Rejected withdrawal tx {} of client {}, requested {} with {} available",
+                        transaction.tx,
+                        client_id,
+                        transaction.amount,
+                        client.available
+                    );
+                    self.report.rejected_withdrawals.push(RejectedWithdrawal {
+                        client: client_id,
+                        tx: transaction.tx,
+                        requested: transaction.amount,
+                        available: client.available,
+                    });
+                    *client
+                })
+            }
+            tx_type => client.new_transaction(tx_type, transaction.amount),
+        };
+
+        match (key, transaction.tx_type) {
+            (Some(key), TransactionType::Deposit) => {
+                self.deposits
+                    .insert(key.clone(), (client_id, transaction.tx, transaction.amount));
+                self.replay(&key);
+            }
+            //Only a withdrawal that was made can be disputed
+            (Some(key), TransactionType::Withdrawal)
+                if self.clients.get(&client_id).map(|c| c.available) != Some(available) =>
+            {
+                self.withdrawals
+                    .insert(key.clone(), (client_id, transaction.tx, transaction.amount));
+                self.replay(&key);
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply the disputes, resolves and chargebacks that came before the transaction they reference,
+    /// in the order they came. They are no longer unmatched.
+    fn replay(&mut self, key: &str) {
+        if let Some(pending) = self.pending.remove(key) {
+            self.unmatched
+                .retain(|unmatched| unmatched.reference.as_deref() != Some(key));
+            for transaction in pending {
+                self.apply_counted(&transaction);
+            }
+        }
+    }
+
+    /// Move the amount of a transfer from its client to the counterparty, creating the clients when the IDs are new.
+    /// The transfer is rejected, changing neither client, if the source doesn't have the amount available
+    /// or one of the clients is locked.
+    fn apply_transfer(&mut self, transaction: &Transaction) {
+        //The source is created like for any other row, even if the transfer has nowhere to go
+        let to_id: u32 = transaction.counterparty.unwrap_or(transaction.client);
+
+        for id in [transaction.client, to_id] {
+            if let Entry::Vacant(entry) = self.clients.entry(id) {
+                entry.insert(Client::new(id));
+                self.order.push(id);
+            }
+        }
+
+        //A transfer to the same client changes nothing
+        if transaction.client == to_id {
+            return;
+        }
+
+        let from: Client = self.clients[&transaction.client];
+        let to: Client = self.clients[&to_id];
+        //The transfer is skipped for each of its clients that is locked
+        for client in [from, to].iter().filter(|c| c.locked) {
+            *self.report.skipped_locked.entry(client.client).or_default() += 1;
+        }
+        if let Some((from, to)) = from.transfer(to, transaction.amount) {
+            self.clients.insert(transaction.client, from);
+            self.clients.insert(to_id, to);
+        }
+    }
+
+    /// Read a single CSV record without header, like `deposit, 1, 1, 1.0`, apply it and
+    /// return the client it's for. The line number is the one given in the errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`TransactionError`] if the line can't be read into a transaction, nothing is applied then.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::ledger::ClientLedger;
+    /// let mut ledger: ClientLedger = ClientLedger::new();
+    /// ledger.apply_csv_line("deposit, 1, 1, 1.0", 1).unwrap();
+    /// assert!(ledger.apply_csv_line("deposit, one, 2, 1.0", 2).is_err());
+    /// ```
+    pub fn apply_csv_line(
+        &mut self,
+        line: &str,
+        line_number: u64,
+    ) -> Result<&Client, TransactionError> {
+        let mut record: csv::StringRecord = csv::StringRecord::new();
+        self.config
+            .csv_reader()
+            .has_headers(false)
+            .from_reader(line.as_bytes())
+            .read_record(&mut record)?;
+
+        let mut position: csv::Position = csv::Position::new();
+        position.set_line(line_number);
+        record.set_position(Some(position));
+
+        let transaction: Transaction =
+            Transaction::new_from_record(&record, &Columns::default(), &self.config)?;
+        self.apply(&transaction);
+
+        Ok(&self.clients[&transaction.client])
+    }
+
+    /// Returns a warning if the clients are more than `client_ratio_warning` times the rows applied.
+    /// Almost a client per transaction often means the client and tx columns are swapped, or bad data.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::ledger::ClientLedger;
+    /// # use accounts_cli::config::models::Config;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let config: Config = Config {
+    ///     client_ratio_warning: Some("0.5".parse().unwrap()),
+    ///     ..Config::default()
+    /// };
+    /// let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+    /// ledger.apply(&Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap()));
+    /// assert!(ledger.client_ratio_warning(1).is_some());
+    /// ```
+    pub fn client_ratio_warning(&self, rows: u64) -> Option<String> {
+        let ratio: Amount = self.config.client_ratio_warning?;
+        let clients: u64 = self.clients.len() as u64;
+
+        if i128::from(clients) * i128::from(Amount::SCALE)
+            > i128::from(rows) * i128::from(ratio.scaled())
+        {
+            Some(format!(
+                "Warning: {} clients for {} transactions, more than {} per transaction, check that the client and tx columns are not swapped",
+                clients, rows, ratio
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the disputes, resolves and chargebacks whose deposit was not found, in the order they
+    /// were applied. They are only kept with `report_unmatched`, otherwise it's always empty.
+    pub fn unmatched(&self) -> &[Unmatched] {
+        &self.unmatched
+    }
+
+    /// Returns the clients in the order they were first seen, keeping the ledger.
+    /// With `split_currencies` they are followed by the clients of each currency, by its code.
+    pub fn clients(&self) -> impl Iterator<Item = &Client> {
+        self.order
+            .iter()
+            .filter_map(|id| self.clients.get(id))
+            .chain(
+                self.split
+                    .values()
+                    .flat_map(|ledger| ledger.order.iter().filter_map(|id| ledger.clients.get(id))),
+            )
+    }
+
+    /// Returns the ledger that applies the transaction: with `split_currencies` the one of its currency,
+    /// see [`ClientLedger::apply`], otherwise this one. Its clients have the balances in that currency.
+    pub fn ledger_for(&self, transaction: &Transaction) -> &ClientLedger {
+        self.split_currency(transaction)
+            .and_then(|code| self.split.get(&code))
+            .unwrap_or(self)
+    }
+
+    /// Returns the currency whose ledger applies the transaction with `split_currencies`, [`None`] for this one.
+    /// A dispute, resolve or chargeback without a currency goes with the transaction it references.
+    fn split_currency(&self, transaction: &Transaction) -> Option<String> {
+        if !self.config.split_currencies {
+            return None;
+        }
+        if transaction.currency.is_some() || !transaction.is_dispute() {
+            return transaction.currency.clone();
+        }
+
+        let key: String = transaction.dispute_key(self.config.dispute_key)?;
+        let references = |ledger: &ClientLedger| {
+            ledger.deposits.contains_key(&key) || ledger.withdrawals.contains_key(&key)
+        };
+        if references(self) {
+            return None;
+        }
+        self.split
+            .iter()
+            .find(|(_, ledger)| references(ledger))
+            .map(|(code, _)| code.clone())
+    }
+
+    /// Returns the client with the ID, if it had any transaction.
+    pub fn get(&self, client: u32) -> Option<&Client> {
+        self.clients.get(&client)
+    }
+
+    /// Returns the currency of the client, the first one found in its transactions.
+    pub fn currency(&self, client: u32) -> Option<&str> {
+        self.currencies.get(&client).map(|c| c.as_str())
+    }
+
+    /// Returns the state of the dispute of the transaction of the client, [`None`] if it was never disputed.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::ledger::{ClientLedger, DisputeState};
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let mut ledger: ClientLedger = ClientLedger::new();
+    /// ledger.apply(&Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap()));
+    /// ledger.apply(&Transaction::new(TransactionType::Dispute, 1, 1, "0.0".parse().unwrap()));
+    /// assert_eq!(ledger.dispute_state(1, 1), Some(DisputeState::Disputed));
+    /// ```
+    pub fn dispute_state(&self, client: u32, tx: u32) -> Option<DisputeState> {
+        self.disputes.get(&(client, tx)).copied()
+    }
+
+    /// Returns the held amount of each open dispute of the client, by the tx ID of the deposit.
+    /// Disputes that don't reference an applied deposit are not in the breakdown.
+    pub fn held_by_dispute(&self, client: u32) -> Option<&BTreeMap<u32, Amount>> {
+        self.held.get(&client).filter(|held| !held.is_empty())
+    }
+
+    /// Returns the transactions skipped so far, like the ones for locked clients,
+    /// and the withdrawals rejected for missing funds.
+    pub fn report(&self) -> &ProcessReport {
+        &self.report
+    }
+
+    /// Add the clients of another ledger with the same [`Config`], like the one of another shard of the
+    /// clients, then put all of them in the order of `first_seen`, the index of the first transaction of each client.
+    /// The ledgers must not share clients nor transactions. The rejected withdrawals of the report are put
+    /// in the order of `applied`, the index of each transaction by tx ID.
+    pub(crate) fn merge(
+        &mut self,
+        other: ClientLedger,
+        first_seen: &HashMap<u32, usize>,
+        applied: &HashMap<u32, usize>,
+    ) {
+        self.clients.extend(other.clients);
+        self.order.extend(other.order);
+        self.deposits.extend(other.deposits);
+        self.withdrawals.extend(other.withdrawals);
+        self.held.extend(other.held);
+        self.disputes.extend(other.disputes);
+        self.opening.extend(other.opening);
+        self.currencies.extend(other.currencies);
+        self.unmatched.extend(other.unmatched);
+        self.pending.extend(other.pending);
+        #[cfg(debug_assertions)]
+        self.seen.extend(other.seen);
+        for (client, skipped) in other.report.skipped_locked {
+            *self.report.skipped_locked.entry(client).or_default() += skipped;
+        }
+        self.report
+            .rejected_withdrawals
+            .extend(other.report.rejected_withdrawals);
+        self.report
+            .rejected_withdrawals
+            .sort_by_key(|rejected| applied.get(&rejected.tx).copied().unwrap_or(usize::MAX));
+
+        self.order
+            .sort_by_key(|id| first_seen.get(id).copied().unwrap_or(usize::MAX));
+    }
+
+    /// Returns the clients in the order they were first seen, then the ones of each currency like [`ClientLedger::clients`].
+    /// The order never depends on the iteration order of the [`HashMap`], so the same input gives the same output.
+    pub fn into_clients(mut self) -> Vec<Client> {
+        let mut clients: Vec<Client> = self
+            .order
+            .iter()
+            .filter_map(|id| self.clients.remove(id))
+            .collect();
+
+        //Every client must be in the order exactly once
+        debug_assert!(
+            self.clients.is_empty(),
+            "a client is missing from the order"
+        );
+        debug_assert_eq!(
+            clients.len(),
+            self.order.len(),
+            "a client is in the order twice"
+        );
+        //A client is created for every ID of the transactions and none without them.
+        //With split_currencies each currency is checked by its own ledger
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            clients.iter().map(|c| c.client).collect::<HashSet<u32>>(),
+            self.seen,
+            "a client was dropped or added"
+        );
+
+        clients.extend(
+            self.split
+                .into_values()
+                .flat_map(|ledger| ledger.into_clients()),
+        );
+        clients
+    }
+}
+
+/// The state a resolve or a chargeback leaves its dispute in.
+fn settled(tx_type: TransactionType) -> DisputeState {
+    match tx_type {
+        TransactionType::Chargeback => DisputeState::ChargedBack,
+        _ => DisputeState::Resolved,
+    }
+}
+
+/// Returns if the amount of a dispute is higher than the deposit times the ratio, computed exactly.
+fn exceeds_ratio(disputed: Amount, deposit: Amount, ratio: Amount) -> bool {
+    i128::from(disputed.scaled()) * i128::from(Amount::SCALE)
+        > i128::from(deposit.scaled()) * i128::from(ratio.scaled())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::config::models::{DisputeKey, ProcessOrder};
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    //Apply a transaction of the client and return the client after it
+    fn apply(
+        ledger: &mut ClientLedger,
+        tx_type: &str,
+        client: u32,
+        tx: u32,
+        value: &str,
+    ) -> Client {
+        ledger.apply(&Transaction::new(
+            TransactionType::parse(tx_type).unwrap(),
+            client,
+            tx,
+            amount(value),
+        ));
+        *ledger.get(client).unwrap()
+    }
+
+    #[test]
+    fn apply_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            2,
+            1,
+            amount("2.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            2,
+            amount("1.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            2,
+            1,
+            amount("0.0"),
+        ));
+
+        assert_eq!(ledger.get(2).unwrap().held, amount("2.0"));
+        assert_eq!(ledger.get(3), None);
+
+        let clients: Vec<Client> = ledger.into_clients();
+        assert_eq!(clients[0].client, 2);
+        assert_eq!(clients[1].client, 1);
+    }
+
+    #[test]
+    fn deterministic_order_test() {
+        let transactions: Vec<Transaction> = (0..1000)
+            .map(|id| {
+                Transaction::new(
+                    TransactionType::Deposit,
+                    (id * 7919) % 500,
+                    id,
+                    amount("1.0"),
+                )
+            })
+            .collect();
+
+        let run = || {
+            //Every ledger has a new random state for its maps
+            let mut ledger: ClientLedger = ClientLedger::new();
+            for transaction in &transactions {
+                ledger.apply(transaction);
+            }
+            ledger.into_clients()
+        };
+
+        let first: Vec<Client> = run();
+        for _ in 0..50 {
+            assert_eq!(run(), first);
+        }
+    }
+
+    #[test]
+    fn dispute_key_test() {
+        let mut deposit: Transaction =
+            Transaction::new(TransactionType::Deposit, 1, 1, amount("10.0"));
+        deposit.reference = Some("A-1".to_string());
+        let mut dispute: Transaction =
+            Transaction::new(TransactionType::Dispute, 1, 2, amount("0.0"));
+        dispute.reference = Some("A-1".to_string());
+
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&deposit);
+        ledger.apply(&dispute);
+        assert_eq!(ledger.get(1).unwrap().held, amount("0.0"));
+
+        let config: Config = Config {
+            dispute_key: DisputeKey::Ref,
+            ..Config::default()
+        };
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        ledger.apply(&deposit);
+        ledger.apply(&dispute);
+        assert_eq!(ledger.get(1).unwrap().available, amount("0.0"));
+        assert_eq!(ledger.get(1).unwrap().held, amount("10.0"));
+    }
+
+    #[test]
+    fn partial_resolve_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("10.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            1,
+            1,
+            amount("4.0"),
+        ));
+        assert_eq!(ledger.get(1).unwrap().available, amount("4.0"));
+        assert_eq!(ledger.get(1).unwrap().held, amount("6.0"));
+        assert_eq!(
+            ledger.held_by_dispute(1).unwrap().get(&1),
+            Some(&amount("6.0"))
+        );
+
+        //More than what is held is ignored
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            1,
+            1,
+            amount("7.0"),
+        ));
+        assert_eq!(ledger.get(1).unwrap().held, amount("6.0"));
+
+        //A resolve without amount frees the rest
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.available, amount("10.0"));
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("10.0"));
+        assert_eq!(ledger.held_by_dispute(1), None);
+    }
+
+    #[test]
+    fn clamp_negative_total_test() {
+        let transactions: Vec<Transaction> = vec![
+            Transaction::new(TransactionType::Deposit, 1, 1, amount("10.0")),
+            Transaction::new(TransactionType::Withdrawal, 1, 2, amount("8.0")),
+            Transaction::new(TransactionType::Dispute, 1, 1, amount("0.0")),
+            Transaction::new(TransactionType::Chargeback, 1, 1, amount("0.0")),
+        ];
+
+        let mut ledger: ClientLedger = ClientLedger::new();
+        for transaction in &transactions {
+            ledger.apply(transaction);
+        }
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.available, amount("-8.0"));
+        assert_eq!(client.total, amount("-8.0"));
+        assert_eq!(client.clamped(), amount("0.0"));
+
+        let config: Config = Config {
+            clamp_negative_total: true,
+            ..Config::default()
+        };
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        for transaction in &transactions {
+            ledger.apply(transaction);
+        }
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.available, amount("0.0"));
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("0.0"));
+        assert_eq!(client.clamped(), amount("8.0"));
+        assert!(client.locked);
+    }
+
+    #[test]
+    fn dispute_resolve_lifecycle_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+
+        //The total stays the same while the funds move to held and back
+        let mut steps: Vec<(Amount, Amount, Amount)> = Vec::new();
+        for (tx_type, value) in [("deposit", "100.0"), ("dispute", "0.0"), ("resolve", "0.0")] {
+            let client: Client = apply(&mut ledger, tx_type, 1, 1, value);
+            assert_eq!(client.total, amount("100.0"));
+            assert!(!client.locked);
+            steps.push((client.available, client.held, client.total));
+        }
+        assert_eq!(
+            steps,
+            [
+                (amount("100.0"), amount("0.0"), amount("100.0")),
+                (amount("0.0"), amount("100.0"), amount("100.0")),
+                (amount("100.0"), amount("0.0"), amount("100.0")),
+            ]
+        );
+        assert_eq!(ledger.held_by_dispute(1), None);
+    }
+
+    #[test]
+    fn dispute_chargeback_lifecycle_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+
+        apply(&mut ledger, "deposit", 1, 1, "100.0");
+        let client: Client = apply(&mut ledger, "dispute", 1, 1, "0.0");
+        assert_eq!(client.held, amount("100.0"));
+        assert_eq!(client.total, amount("100.0"));
+
+        let client: Client = apply(&mut ledger, "chargeback", 1, 1, "0.0");
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("0.0"));
+        assert!(client.locked);
+
+        //The deposit after the lock is ignored
+        let client: Client = apply(&mut ledger, "deposit", 1, 2, "50.0");
+        assert_eq!(client.available, amount("0.0"));
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("0.0"));
+        assert!(client.locked);
+        assert_eq!(client.locked_at_tx, Some(1));
+    }
+
+    #[test]
+    fn dispute_amount_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+
+        //The dispute holds the amount of the deposit, not the one of its row
+        apply(&mut ledger, "deposit", 1, 1, "50.0");
+        let client: Client = apply(&mut ledger, "dispute", 1, 1, "0.0");
+        assert_eq!(client.available, amount("0.0"));
+        assert_eq!(client.held, amount("50.0"));
+        assert_eq!(client.total, amount("50.0"));
+
+        //A dispute that creates the client of an unknown deposit holds nothing
+        let client: Client = apply(&mut ledger, "dispute", 2, 9, "20.0");
+        assert_eq!(client.available, amount("0.0"));
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("0.0"));
+        assert_eq!(client.type_counts().disputes, 1);
+
+        //So does a dispute of an unknown deposit of a known client
+        let client: Client = apply(&mut ledger, "dispute", 1, 9, "20.0");
+        assert_eq!(client.held, amount("50.0"));
+        assert_eq!(client.total, amount("50.0"));
+    }
+
+    #[test]
+    fn resolve_without_dispute_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        let balances =
+            |client: Client| (client.available, client.held, client.total, client.locked);
+
+        //Without a dispute, the resolve and the chargeback don't touch the held funds
+        let deposited: Client = apply(&mut ledger, "deposit", 1, 1, "10.0");
+        assert_eq!(
+            balances(apply(&mut ledger, "resolve", 1, 1, "0.0")),
+            balances(deposited)
+        );
+        assert_eq!(
+            balances(apply(&mut ledger, "chargeback", 1, 1, "0.0")),
+            balances(deposited)
+        );
+        assert_eq!(ledger.dispute_state(1, 1), None);
+    }
+
+    #[test]
+    fn double_chargeback_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        let balances =
+            |client: Client| (client.available, client.held, client.total, client.locked);
+
+        apply(&mut ledger, "deposit", 1, 1, "10.0");
+        apply(&mut ledger, "deposit", 1, 2, "5.0");
+        apply(&mut ledger, "dispute", 1, 1, "0.0");
+        apply(&mut ledger, "dispute", 1, 2, "0.0");
+        let charged_back: Client = apply(&mut ledger, "chargeback", 1, 1, "0.0");
+        assert_eq!(charged_back.held, amount("5.0"));
+        assert_eq!(charged_back.total, amount("5.0"));
+
+        //The second chargeback must not take the held funds of the other dispute
+        assert_eq!(
+            balances(apply(&mut ledger, "chargeback", 1, 1, "0.0")),
+            balances(charged_back)
+        );
+        assert_eq!(
+            balances(apply(&mut ledger, "resolve", 1, 1, "0.0")),
+            balances(charged_back)
+        );
+        assert_eq!(ledger.dispute_state(1, 1), Some(DisputeState::ChargedBack));
+        assert_eq!(ledger.dispute_state(1, 2), Some(DisputeState::Disputed));
+    }
+
+    #[test]
+    fn outcome_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        let mut apply = |tx_type: &str, tx: u32, value: &str| -> Outcome {
+            ledger.apply(&Transaction::new(
+                TransactionType::parse(tx_type).unwrap(),
+                1,
+                tx,
+                amount(value),
+            ))
+        };
+
+        assert_eq!(apply("withdrawal", 1, "1.0"), Outcome::Rejected);
+        assert_eq!(apply("deposit", 2, "1.0"), Outcome::Applied);
+        assert_eq!(apply("withdrawal", 3, "2.0"), Outcome::Rejected);
+        assert_eq!(apply("dispute", 9, "0.0"), Outcome::Rejected);
+        assert_eq!(apply("dispute", 2, "0.0"), Outcome::Applied);
+        assert_eq!(apply("dispute", 2, "0.0"), Outcome::Rejected);
+        assert_eq!(apply("chargeback", 2, "0.0"), Outcome::Applied);
+        assert_eq!(apply("deposit", 4, "1.0"), Outcome::Rejected);
+    }
+
+    #[test]
+    fn withdrawal_dispute_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        let balances = |client: Client| (client.available, client.held, client.total);
+
+        apply(&mut ledger, "deposit", 1, 1, "100.0");
+        let withdrawn: Client = apply(&mut ledger, "withdrawal", 1, 2, "30.0");
+        assert_eq!(withdrawn.available, amount("70.0"));
+
+        //The claim holds the amount, available stays at the one after the withdrawal
+        let disputed: Client = apply(&mut ledger, "dispute", 1, 2, "0.0");
+        assert_eq!(
+            balances(disputed),
+            (amount("70.0"), amount("30.0"), amount("100.0"))
+        );
+        assert_eq!(
+            balances(apply(&mut ledger, "dispute", 1, 2, "0.0")),
+            balances(disputed)
+        );
+
+        //The claim is rejected, the withdrawal stands and nothing is counted twice
+        let resolved: Client = apply(&mut ledger, "resolve", 1, 2, "0.0");
+        assert_eq!(balances(resolved), balances(withdrawn));
+        assert_eq!(
+            balances(apply(&mut ledger, "resolve", 1, 2, "0.0")),
+            balances(withdrawn)
+        );
+
+        //Disputed again and charged back, the amount is returned
+        apply(&mut ledger, "dispute", 1, 2, "0.0");
+        let charged: Client = apply(&mut ledger, "chargeback", 1, 2, "0.0");
+        assert_eq!(
+            balances(charged),
+            (amount("100.0"), amount("0.0"), amount("100.0"))
+        );
+        assert!(charged.locked);
+
+        //A withdrawal without the funds was not made and can't be disputed
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Withdrawal,
+            2,
+            3,
+            amount("5.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            2,
+            3,
+            amount("0.0"),
+        ));
+        assert_eq!(ledger.get(2).unwrap().held, amount("0.0"));
+    }
+
+    #[test]
+    fn several_disputes_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        for (tx, value) in [(1, "10.0"), (2, "5.0"), (3, "2.5")] {
+            ledger.apply(&Transaction::new(
+                TransactionType::Deposit,
+                1,
+                tx,
+                amount(value),
+            ));
+        }
+        for tx in 1..=3 {
+            ledger.apply(&Transaction::new(
+                TransactionType::Dispute,
+                1,
+                tx,
+                amount("0.0"),
+            ));
+        }
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.available, amount("0.0"));
+        assert_eq!(client.held, amount("17.5"));
+
+        let resolve = |ledger: &mut ClientLedger, tx: u32, value: &str| -> Client {
+            ledger.apply(&Transaction::new(
+                TransactionType::Resolve,
+                1,
+                tx,
+                amount(value),
+            ));
+            *ledger.get(1).unwrap()
+        };
+
+        //Part of the second dispute
+        let client: Client = resolve(&mut ledger, 2, "2.0");
+        assert_eq!(client.available, amount("2.0"));
+        assert_eq!(client.held, amount("15.5"));
+
+        //The whole first one, the others stay held
+        let client: Client = resolve(&mut ledger, 1, "0.0");
+        assert_eq!(client.available, amount("12.0"));
+        assert_eq!(client.held, amount("5.5"));
+        assert_eq!(ledger.held_by_dispute(1).unwrap().get(&1), None);
+        assert_eq!(ledger.held_by_dispute(1).unwrap()[&2], amount("3.0"));
+        assert_eq!(ledger.held_by_dispute(1).unwrap()[&3], amount("2.5"));
+
+        //The rest of the second one
+        let client: Client = resolve(&mut ledger, 2, "0.0");
+        assert_eq!(client.available, amount("15.0"));
+        assert_eq!(client.held, amount("2.5"));
+
+        //And the third one
+        let client: Client = resolve(&mut ledger, 3, "0.0");
+        assert_eq!(client.available, amount("17.5"));
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("17.5"));
+        assert_eq!(ledger.held_by_dispute(1), None);
+    }
+
+    #[test]
+    fn repeated_dispute_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("10.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            2,
+            amount("5.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.available, amount("5.0"));
+        assert_eq!(client.held, amount("10.0"));
+        assert_eq!(client.total, amount("15.0"));
+        assert_eq!(client.type_counts().disputes, 2);
+        assert_eq!(ledger.held_by_dispute(1).unwrap()[&1], amount("10.0"));
+
+        //Once resolved, the deposit can be disputed again
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        assert_eq!(ledger.get(1).unwrap().held, amount("10.0"));
+    }
+
+    #[test]
+    fn max_dispute_ratio_test() {
+        let config: Config = Config {
+            max_dispute_ratio: Some(amount("1.1")),
+            ..Config::default()
+        };
+        let dispute = |client: u32, tx: u32, value: &str| -> Client {
+            let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+            ledger.apply(&Transaction::new(
+                TransactionType::Deposit,
+                client,
+                tx,
+                amount("10.0"),
+            ));
+            ledger.apply(&Transaction::new(
+                TransactionType::Dispute,
+                client,
+                tx,
+                amount(value),
+            ));
+            *ledger.get(client).unwrap()
+        };
+
+        //Up to 11.0 is within the ratio, and the deposit amount is held
+        assert_eq!(dispute(1, 1, "11.0").held, amount("10.0"));
+        assert_eq!(dispute(1, 1, "0.0").held, amount("10.0"));
+
+        let rejected: Client = dispute(1, 1, "11.0001");
+        assert_eq!(rejected.held, amount("0.0"));
+        assert_eq!(rejected.available, amount("10.0"));
+
+        assert!(!exceeds_ratio(amount("5.0"), amount("10.0"), amount("0.5")));
+        assert!(exceeds_ratio(
+            amount("0.0001"),
+            amount("10.0"),
+            amount("0.0")
+        ));
+    }
+
+    #[test]
+    fn seed_test() {
+        let seeded: Vec<Client> = vec![
+            Client::new(1).new_transaction(TransactionType::Deposit, amount("5.0")),
+            Client::new(2).new_transaction(TransactionType::Deposit, amount("3.0")),
+        ];
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.seed(seeded.clone());
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            3,
+            1,
+            amount("1.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Withdrawal,
+            2,
+            2,
+            amount("1.0"),
+        ));
+
+        let clients: Vec<Client> = ledger.into_clients();
+        assert_eq!(clients[0], seeded[0]);
+        assert_eq!(clients[1].available, amount("2.0"));
+        assert_eq!(clients[2].client, 3);
+    }
+
+    #[test]
+    fn client_ids_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.seed([Client::new(5)]);
+        let mut transfer: Transaction =
+            Transaction::new(TransactionType::Transfer, 1, 1, amount("1.0"));
+        transfer.counterparty = Some(2);
+        ledger.apply(&transfer);
+        //The counterparty of a deposit is not a client
+        let mut deposit: Transaction =
+            Transaction::new(TransactionType::Deposit, 3, 2, amount("1.0"));
+        deposit.counterparty = Some(4);
+        ledger.apply(&deposit);
+
+        let ids: Vec<u32> = ledger.into_clients().iter().map(|c| c.client).collect();
+        assert_eq!(ids, [5, 1, 2, 3]);
+
+        //A transfer without a counterparty changes nothing, its source is still a client
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("2.0"),
+        ));
+        let transfer: Transaction =
+            Transaction::new(TransactionType::Transfer, 6, 2, amount("1.0"));
+        assert_eq!(ledger.apply(&transfer), Outcome::Rejected);
+        let clients: Vec<Client> = ledger.into_clients();
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[1].client, 6);
+        assert_eq!(clients[1].total, Amount::ZERO);
+    }
+
+    #[test]
+    fn client_ratio_warning_test() {
+        let config: Config = Config {
+            client_ratio_warning: Some(amount("0.5")),
+            ..Config::default()
+        };
+
+        //Swapped columns, every tx looks like a new client
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        for id in 0..10 {
+            ledger.apply(&Transaction::new(
+                TransactionType::Deposit,
+                id,
+                1,
+                amount("1.0"),
+            ));
+        }
+        assert_eq!(
+            ledger.client_ratio_warning(10),
+            Some("Warning: 10 clients for 10 transactions, more than 0.5000 per transaction, check that the client and tx columns are not swapped".to_string())
+        );
+
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        for id in 0..10 {
+            ledger.apply(&Transaction::new(
+                TransactionType::Deposit,
+                id % 5,
+                id,
+                amount("1.0"),
+            ));
+        }
+        assert_eq!(ledger.client_ratio_warning(10), None);
+
+        //Without the option there is no warning
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("1.0"),
+        ));
+        assert_eq!(ledger.client_ratio_warning(1), None);
+    }
+
+    #[test]
+    fn unmatched_test() {
+        let config: Config = Config {
+            report_unmatched: true,
+            ..Config::default()
+        };
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("1.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            5,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            2,
+            6,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Chargeback,
+            1,
+            7,
+            amount("0.0"),
+        ));
+
+        let unmatched: Vec<(&str, u32, u32)> = ledger
+            .unmatched()
+            .iter()
+            .map(|u| (u.tx_type.as_str(), u.client, u.tx))
+            .collect();
+        assert_eq!(
+            unmatched,
+            [("dispute", 1, 5), ("resolve", 2, 6), ("chargeback", 1, 7)]
+        );
+        assert_eq!(ledger.unmatched()[0].reference, Some("5".to_string()));
+
+        //Without the option they are not kept
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            5,
+            amount("0.0"),
+        ));
+        assert!(ledger.unmatched().is_empty());
+    }
+
+    #[test]
+    fn transfer_test() {
+        let mut transfer: Transaction =
+            Transaction::new(TransactionType::Transfer, 1, 2, amount("4.0"));
+        transfer.counterparty = Some(2);
+
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("10.0"),
+        ));
+        ledger.apply(&transfer);
+        assert_eq!(ledger.get(1).unwrap().available, amount("6.0"));
+        assert_eq!(ledger.get(1).unwrap().total, amount("6.0"));
+        assert_eq!(ledger.get(2).unwrap().available, amount("4.0"));
+        assert_eq!(ledger.get(2).unwrap().total, amount("4.0"));
+
+        //Not enough funds, neither client changes
+        transfer.amount = amount("6.5");
+        ledger.apply(&transfer);
+        assert_eq!(ledger.get(1).unwrap().available, amount("6.0"));
+        assert_eq!(ledger.get(2).unwrap().available, amount("4.0"));
+
+        //The whole balance can be moved
+        transfer.amount = amount("6.0");
+        ledger.apply(&transfer);
+        assert_eq!(ledger.get(1).unwrap().available, amount("0.0"));
+        assert_eq!(ledger.get(2).unwrap().available, amount("10.0"));
+
+        let clients: Vec<Client> = ledger.into_clients();
+        assert_eq!(clients.len(), 2);
+    }
+
+    #[test]
+    fn locked_transfer_test() {
+        let tx: &str = "type, client, tx, amount, counterparty\n\
+            deposit, 1, 1, 10.0\n\
+            deposit, 2, 2, 10.0\n\
+            deposit, 3, 3, 1.0\n\
+            transfer, 1, 4, 3.0, 2\n\
+            transfer, 3, 5, 2.0, 1\n\
+            dispute, 2, 2\n\
+            chargeback, 2, 2\n\
+            transfer, 1, 6, 1.0, 2\n\
+            transfer, 2, 7, 1.0, 1\n";
+        let config: Config = Config {
+            process_order: ProcessOrder::File,
+            ..Config::default()
+        };
+        let result: crate::LedgerResult = crate::process(&config, tx.as_bytes()).unwrap();
+
+        //Client 3 is overdrawn, then the locked client 2 can't send nor receive
+        assert_eq!(
+            result.report,
+            "client,available,held,total,locked\n\
+             1,7.0000,0.0000,7.0000,false\n\
+             2,3.0000,0.0000,3.0000,true\n\
+             3,1.0000,0.0000,1.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn locked_dispute_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("10.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            2,
+            amount("5.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            2,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Chargeback,
+            1,
+            2,
+            amount("0.0"),
+        ));
+        assert!(ledger.get(1).unwrap().locked);
+
+        //New activity is ignored once locked
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            3,
+            amount("3.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            3,
+            amount("0.0"),
+        ));
+        assert_eq!(ledger.get(1).unwrap().available, amount("10.0"));
+        assert_eq!(ledger.get(1).unwrap().held, amount("0.0"));
+
+        //A deposit from before the lock can still be disputed and resolved
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        assert_eq!(ledger.get(1).unwrap().available, amount("0.0"));
+        assert_eq!(ledger.get(1).unwrap().held, amount("10.0"));
+
+        assert_eq!(
+            ledger.held_by_dispute(1).unwrap().get(&1),
+            Some(&amount("10.0"))
+        );
+
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        assert_eq!(ledger.held_by_dispute(1), None);
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.available, amount("10.0"));
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(client.total, amount("10.0"));
+        assert!(client.locked);
+    }
+
+    #[test]
+    fn pending_dispute_test() {
+        let config: Config = Config {
+            report_unmatched: true,
+            ..Config::default()
+        };
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+
+        //The dispute comes before its deposit, like in tx order, and waits for it
+        let client: Client = apply(&mut ledger, "dispute", 1, 3, "0.0");
+        assert_eq!(client.held, amount("0.0"));
+        assert_eq!(ledger.unmatched().len(), 1);
+        apply(&mut ledger, "deposit", 1, 2, "1.0");
+
+        let client: Client = apply(&mut ledger, "deposit", 1, 3, "5.0");
+        assert_eq!(client.available, amount("1.0"));
+        assert_eq!(client.held, amount("5.0"));
+        assert_eq!(client.total, amount("6.0"));
+        assert_eq!(ledger.unmatched().len(), 0);
+        assert_eq!(client.counts.disputes, 1);
+
+        //A chargeback before the deposit of another dispute is applied after the rows before it
+        apply(&mut ledger, "chargeback", 1, 4, "0.0");
+        apply(&mut ledger, "dispute", 1, 4, "0.0");
+        let client: Client = apply(&mut ledger, "deposit", 1, 4, "2.0");
+        assert_eq!(client.held, amount("7.0"));
+        assert!(!client.locked);
+        assert_eq!(ledger.dispute_state(1, 4), Some(DisputeState::Disputed));
+    }
+
+    #[test]
+    fn fee_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        let mut apply = |tx_type: TransactionType, client: u32, tx: u32, value: &str| {
+            ledger.apply(&Transaction::new(tx_type, client, tx, amount(value)))
+        };
+        apply(TransactionType::Deposit, 1, 1, "2.0");
+        assert_eq!(apply(TransactionType::Fee, 1, 2, "3.0"), Outcome::Applied);
+        apply(TransactionType::Deposit, 2, 3, "5.0");
+        apply(TransactionType::Dispute, 2, 3, "0.0");
+        apply(TransactionType::Chargeback, 2, 3, "0.0");
+
+        //The fee of a locked client is skipped like its other transactions
+        assert_eq!(apply(TransactionType::Fee, 2, 4, "1.0"), Outcome::Rejected);
+
+        assert_eq!(ledger.get(1).unwrap().available, amount("-1.0"));
+        assert_eq!(ledger.get(2).unwrap().available, amount("0.0"));
+        assert_eq!(ledger.report().skipped_locked.get(&2), Some(&1));
+    }
+
+    #[test]
+    fn other_client_dispute_test() {
+        for dispute_key in [DisputeKey::Tx, DisputeKey::Ref] {
+            let mut ledger: ClientLedger = ClientLedger::with_config(&Config {
+                dispute_key,
+                ..Config::default()
+            });
+            let mut apply = |tx_type: TransactionType, client: u32, tx: u32, value: &str| {
+                ledger.apply(&Transaction {
+                    reference: Some(format!("r{}", tx)),
+                    ..Transaction::new(tx_type, client, tx, amount(value))
+                })
+            };
+            apply(TransactionType::Deposit, 1, 1, "10.0");
+            apply(TransactionType::Deposit, 2, 2, "1.0");
+
+            //Client 2 disputes the deposit of client 1, tx 1 and ref r1
+            assert_eq!(
+                apply(TransactionType::Dispute, 2, 1, "0.0"),
+                Outcome::Rejected
+            );
+            assert_eq!(
+                apply(TransactionType::Chargeback, 2, 1, "0.0"),
+                Outcome::Rejected
+            );
+
+            let client: &Client = ledger.get(1).unwrap();
+            assert_eq!(
+                (client.available, client.held),
+                (amount("10.0"), Amount::ZERO)
+            );
+            assert!(!client.locked);
+            let client: &Client = ledger.get(2).unwrap();
+            assert_eq!(
+                (client.available, client.held),
+                (amount("1.0"), Amount::ZERO)
+            );
+            assert!(!client.locked);
+        }
+    }
+
+    #[test]
+    fn many_disputes_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        for tx in 1..=5_000 {
+            ledger.apply(&Transaction::new(
+                TransactionType::Deposit,
+                tx % 10,
+                tx,
+                Amount::from_scaled(tx as i64 * Amount::SCALE),
+            ));
+        }
+        //Every deposit is disputed, the even ones are charged back and the odd ones resolved
+        for tx in 1..=5_000 {
+            ledger.apply(&Transaction::new(
+                TransactionType::Dispute,
+                tx % 10,
+                tx,
+                Amount::ZERO,
+            ));
+        }
+        for tx in 1..=5_000 {
+            let tx_type: TransactionType = if tx % 2 == 0 {
+                TransactionType::Chargeback
+            } else {
+                TransactionType::Resolve
+            };
+            ledger.apply(&Transaction::new(tx_type, tx % 10, tx, Amount::ZERO));
+        }
+
+        for client in 0..10u32 {
+            //The odd clients only have odd deposits, kept, the even clients lose them all
+            let kept: i64 = (1..=5_000)
+                .filter(|tx| tx % 10 == client && tx % 2 == 1)
+                .map(|tx| tx as i64)
+                .sum();
+            let client: &Client = ledger.get(client).unwrap();
+            assert_eq!(client.available, Amount::from_scaled(kept * Amount::SCALE));
+            assert_eq!(client.held, Amount::ZERO);
+            assert_eq!(client.locked, client.client.is_multiple_of(2));
+        }
+    }
+
+    //Keeps the messages logged by every test, the logger is set once for the whole binary
+    struct CaptureLogger(std::sync::Mutex<Vec<String>>);
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger(std::sync::Mutex::new(Vec::new()));
+
+    #[test]
+    fn log_test() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            901,
+            1,
+            amount("1.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Withdrawal,
+            901,
+            2,
+            amount("5.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            901,
+            9,
+            Amount::ZERO,
+        ));
+
+        let logs: Vec<String> = LOGGER.0.lock().unwrap().clone();
+        assert!(logs.contains(
+            &"Rejected withdrawal tx 2 of client 901, requested 5.0000 with 1.0000 available"
+                .to_string()
+        ));
+        assert!(logs.contains(&"dispute tx 9 for client 901 references 9, not found".to_string()));
+    }
+}
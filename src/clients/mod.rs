@@ -1 +1,4 @@
+pub mod ledger;
 pub mod models;
+pub mod output;
+pub mod timeline;
@@ -1,46 +1,267 @@
-use super::super::transactions::models::Transaction;
+use super::super::config::models::Config;
+use super::super::money::Amount;
+use super::super::transactions::models::{Transaction, TransactionError, TransactionType};
+use super::ledger::{ClientLedger, ProcessReport};
 use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
 
 /// Implementation of Client for CSV
 /// # Examples
 /// To create a new Client you can use [`Client::new(client_id)`]:
 /// ```
+/// # use accounts_cli::clients::models::Client;
+/// # let client_id: u32 = 1;
 /// let mut new_client: Client = Client::new(client_id);
 /// ```
 ///
 /// Also a Client is created when the transactions are processed and a new Client ID is found.
 #[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub struct Client {
-    client: u32,
-    available: f32,
-    held: f32,
-    total: f32,
-    locked: bool,
+    pub(super) client: u32,
+    pub(super) available: Amount,
+    pub(super) held: Amount,
+    pub(super) total: Amount,
+    pub(super) locked: bool,
+    #[serde(skip)]
+    pub(super) counts: TypeCounts,
+    /// Amount added to keep the total at zero with `--clamp-negative-total`.
+    #[serde(skip)]
+    pub(super) clamped: Amount,
+    /// Why the client was locked, for `--with-locked-reason`.
+    #[serde(skip)]
+    pub(super) locked_reason: Option<LockReason>,
+    /// Tx ID of the transaction that locked the client.
+    #[serde(skip)]
+    pub(super) locked_at_tx: Option<u32>,
+}
+
+/// Why a client was locked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockReason {
+    /// A chargeback reversed one of its deposits.
+    Chargeback,
+}
+
+/// The funds of a client, without its ID and lock, see [`Client::balances`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct Balances {
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+}
+
+/// Number of transactions of each type a client had, for `--with-type-counts`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct TypeCounts {
+    pub deposits: u32,
+    pub withdrawals: u32,
+    pub disputes: u32,
+    pub resolves: u32,
+    pub chargebacks: u32,
+}
+
+impl TypeCounts {
+    /// Count one more transaction of the type, transfers and fees are not counted.
+    pub fn add(&mut self, tx_type: TransactionType) {
+        match tx_type {
+            TransactionType::Deposit => self.deposits += 1,
+            TransactionType::Withdrawal => self.withdrawals += 1,
+            TransactionType::Dispute => self.disputes += 1,
+            TransactionType::Resolve => self.resolves += 1,
+            TransactionType::Chargeback => self.chargebacks += 1,
+            TransactionType::Transfer | TransactionType::Fee => {}
+        }
+    }
 }
 
 impl Client {
-    /// Returns a Serialize String with all the users
-    pub fn clients_to_csv(clients: Vec<Client>) -> String {
-        let mut clients_csv: csv::Writer<Vec<u8>> = csv::Writer::from_writer(vec![]);
+    /// Returns a Serialize String with all the users, sorted by ID so the same clients always give the same CSV.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`csv::Error`] if a client can't be serialized, see [`Client::clients_to_writer`].
+    pub fn clients_to_csv(mut clients: Vec<Client>) -> Result<String, csv::Error> {
+        let mut data: Vec<u8> = Vec::new();
+
+        Client::sort_by_id(&mut clients);
+        Client::clients_to_writer(clients, &mut data, &Config::default())?;
+
+        String::from_utf8(data)
+            .map_err(|e| csv::Error::from(io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+
+    /// Sort the clients by ID ascending, for output that can be diffed whatever the order of the input.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// let mut clients: Vec<Client> = vec![Client::new(3), Client::new(1), Client::new(2)];
+    /// Client::sort_by_id(&mut clients);
+    /// assert_eq!(clients.iter().map(|c| c.id()).collect::<Vec<u32>>(), [1, 2, 3]);
+    /// ```
+    pub fn sort_by_id(clients: &mut [Client]) {
+        clients.sort_unstable_by_key(|client| client.client);
+    }
+
+    /// Returns the sum of the totals of all the clients, for the summary of a run.
+    /// Each step of the sum is checked, so many large balances don't wrap around.
+    ///
+    /// Returns [`None`] if the grand total overflows.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// # let transactions: Vec<Transaction> = vec![Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap())];
+    /// let clients: Vec<Client> = Client::process_transactions(&transactions);
+    /// match Client::grand_total(&clients) {
+    ///     Some(total) => println!("Grand total {}", total),
+    ///     None => println!("The grand total overflows"),
+    /// }
+    /// ```
+    pub fn grand_total(clients: &[Client]) -> Option<Amount> {
+        let mut grand_total: Amount = Amount::ZERO;
 
         for client in clients {
-            if let Err(_err) = clients_csv.serialize(&client) {
-                panic!(r#"Error serializing"#);
-            }
+            grand_total = grand_total.checked_add(client.total)?;
         }
 
-        let data: String = String::from_utf8(clients_csv.into_inner().unwrap()).unwrap();
-        return data;
+        Some(grand_total)
     }
 
     /// Create a new empty client, ID is required.
     pub fn new(client: u32) -> Self {
         Self {
             client,
-            available: 0.0,
-            held: 0.0,
-            total: 0.0,
+            available: Amount::ZERO,
+            held: Amount::ZERO,
+            total: Amount::ZERO,
             locked: false,
+            counts: TypeCounts::default(),
+            clamped: Amount::ZERO,
+            locked_reason: None,
+            locked_at_tx: None,
+        }
+    }
+
+    /// Create a client with the balances it had, like the ones of a saved output, the total is
+    /// the available and held funds together. A locked client has no lock reason or tx.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// let client: Client = Client::from_balances(1, "1.5".parse().unwrap(), "0.5".parse().unwrap(), false);
+    /// assert_eq!(client.total(), "2.0".parse().unwrap());
+    /// ```
+    pub fn from_balances(client: u32, available: Amount, held: Amount, locked: bool) -> Self {
+        Self {
+            available,
+            held,
+            total: available + held,
+            locked,
+            ..Client::new(client)
+        }
+    }
+
+    /// Returns how many transactions of each type the client had.
+    pub fn type_counts(&self) -> TypeCounts {
+        self.counts
+    }
+
+    /// Returns the ID of the client.
+    pub fn id(&self) -> u32 {
+        self.client
+    }
+
+    /// Returns the funds available for withdrawals and transfers.
+    pub fn available(&self) -> Amount {
+        self.available
+    }
+
+    /// Returns the funds held by open disputes.
+    pub fn held(&self) -> Amount {
+        self.held
+    }
+
+    /// Returns the available and held funds together.
+    pub fn total(&self) -> Amount {
+        self.total
+    }
+
+    /// Returns if a chargeback locked the client.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Returns why the client was locked, [`None`] if it's not locked.
+    pub fn locked_reason(&self) -> Option<LockReason> {
+        self.locked_reason
+    }
+
+    /// Returns the tx ID of the transaction that locked the client.
+    pub fn locked_at_tx(&self) -> Option<u32> {
+        self.locked_at_tx
+    }
+
+    /// Returns the available, held and total funds together.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::{Balances, Client};
+    /// # use accounts_cli::transactions::models::TransactionType;
+    /// let client: Client = Client::new(1).new_transaction(TransactionType::Deposit, "2.0".parse().unwrap());
+    /// let balances: Balances = client.balances();
+    /// assert_eq!(balances.total, client.total());
+    /// ```
+    pub fn balances(&self) -> Balances {
+        Balances {
+            available: self.available,
+            held: self.held,
+            total: self.total,
+        }
+    }
+
+    /// Returns if the clients have the same balances and lock, the fields of the output.
+    /// The type counts are not compared.
+    pub fn same_balance(&self, other: &Client) -> bool {
+        self.client == other.client
+            && self.available == other.available
+            && self.held == other.held
+            && self.total == other.total
+            && self.locked == other.locked
+    }
+
+    /// Combine two partial balances of the same client, like the ones of two batches of its transactions
+    /// applied apart. The amounts and type counts are summed and the client is locked if any of them was,
+    /// the lock reason is the one of this client if it has one.
+    ///
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::TransactionType;
+    /// let first: Client = Client::new(1).new_transaction(TransactionType::Deposit, "1.0".parse().unwrap());
+    /// let second: Client = Client::new(1).new_transaction(TransactionType::Deposit, "2.0".parse().unwrap());
+    ///
+    /// assert_eq!(first.merge(second).total(), "3.0".parse().unwrap());
+    /// ```
+    pub fn merge(self, other: Client) -> Client {
+        debug_assert_eq!(self.client, other.client, "merging different clients");
+
+        Client {
+            client: self.client,
+            available: self.available + other.available,
+            held: self.held + other.held,
+            total: self.total + other.total,
+            locked: self.locked || other.locked,
+            counts: TypeCounts {
+                deposits: self.counts.deposits + other.counts.deposits,
+                withdrawals: self.counts.withdrawals + other.counts.withdrawals,
+                disputes: self.counts.disputes + other.counts.disputes,
+                resolves: self.counts.resolves + other.counts.resolves,
+                chargebacks: self.counts.chargebacks + other.counts.chargebacks,
+            },
+            clamped: self.clamped + other.clamped,
+            locked_reason: self.locked_reason.or(other.locked_reason),
+            locked_at_tx: self.locked_at_tx.or(other.locked_at_tx),
         }
     }
 
@@ -52,33 +273,46 @@ impl Client {
     ///
     /// ```
     /// use accounts_cli::clients::models::Client;
+    /// use accounts_cli::money::Amount;
+    /// use accounts_cli::transactions::models::TransactionType;
     ///
+    /// # let client_id: u32 = 1;
+    /// let amount: Amount = "1.0".parse().unwrap();
     /// let mut client: Client = Client::new(client_id);
-    /// client = client.new_transaction("deposit", 1.0);
-    /// assert_eq!(client.total, 1.0);
+    /// client = client.new_transaction(TransactionType::Deposit, amount);
+    /// assert_eq!(Client::grand_total(&[client]), Some(amount));
     /// ```
-    pub fn new_transaction(mut self, tx_type: String, amount: f32) -> Self {
-        match tx_type.as_str() {
-            "deposit" => {
+    pub fn new_transaction(mut self, tx_type: TransactionType, amount: Amount) -> Self {
+        match tx_type {
+            TransactionType::Deposit => {
                 self.available += amount;
             }
-            "withdrawal" => {
-                if (self.available - amount) > 0.0 {
-                    self.available -= amount;
+            //A withdrawal without the funds is ignored, see Client::withdraw
+            TransactionType::Withdrawal => {
+                if let Some(client) = self.withdraw(amount) {
+                    self = client;
                 }
             }
-            "dispute" => {
+            TransactionType::Dispute => {
                 self.available -= amount;
                 self.held += amount;
             }
-            "resolve" => {
-                self.available += self.held;
-                self.held -= amount;
+            //Only the amount of the resolved dispute is freed, other disputes stay held.
+            //Like a chargeback, more than held is never freed
+            TransactionType::Resolve => {
+                let freed: Amount = amount.min(self.held);
+                self.available += freed;
+                self.held -= freed;
             }
-            "chargeback" => {
-                self.held -= amount;
+            //The ledger charges back the disputed amount, more than held is never taken
+            TransactionType::Chargeback => {
+                self.held -= amount.min(self.held);
                 self.locked = true;
             }
+            //A fee is taken even if it leaves available below zero
+            TransactionType::Fee => {
+                self.available -= amount;
+            }
             _ => {}
         }
 
@@ -87,73 +321,251 @@ impl Client {
         self
     }
 
+    /// Floor the total at zero if it's negative, the missing amount is added to available and recorded.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::money::Amount;
+    /// # use accounts_cli::transactions::models::TransactionType;
+    /// let amount: Amount = "5.0".parse().unwrap();
+    /// let client: Client = Client::new(1)
+    ///     .new_transaction(TransactionType::Dispute, amount)
+    ///     .new_transaction(TransactionType::Chargeback, amount)
+    ///     .clamp_total();
+    /// assert_eq!(client.clamped(), amount);
+    /// ```
+    pub fn clamp_total(mut self) -> Self {
+        if self.total.is_negative() {
+            self.clamped -= self.total;
+            self.available -= self.total;
+            self.total = self.available + self.held;
+        }
+
+        self
+    }
+
+    /// Returns the amount added by [`Client::clamp_total`] to keep the total at zero.
+    pub fn clamped(&self) -> Amount {
+        self.clamped
+    }
+
+    /// Take the amount from the available funds, the whole available amount can be withdrawn.
+    /// Returns [`None`] if the client doesn't have the amount available, so a rejected withdrawal
+    /// can be told apart from an applied one. The lock is checked by the [`ClientLedger`].
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::money::Amount;
+    /// # use accounts_cli::transactions::models::TransactionType;
+    /// let amount: Amount = "5.0".parse().unwrap();
+    /// let client: Client = Client::new(1).new_transaction(TransactionType::Deposit, amount);
+    ///
+    /// assert_eq!(client.withdraw(amount).unwrap().available(), Amount::ZERO);
+    /// assert_eq!(client.withdraw("5.01".parse().unwrap()), None);
+    /// ```
+    pub fn withdraw(mut self, amount: Amount) -> Option<Client> {
+        if self.available < amount {
+            return None;
+        }
+
+        self.available -= amount;
+        self.total = self.available + self.held;
+
+        Some(self)
+    }
+
+    /// Move the amount from the available funds of this client to the ones of `to`, both or none.
+    /// Returns [`None`] if this client doesn't have the amount available or one of the clients is locked.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::money::Amount;
+    /// # use accounts_cli::transactions::models::TransactionType;
+    /// let amount: Amount = "5.0".parse().unwrap();
+    /// let from: Client = Client::new(1).new_transaction(TransactionType::Deposit, amount);
+    ///
+    /// let (from, to) = from.transfer(Client::new(2), amount).unwrap();
+    /// assert_eq!(from.transfer(to, amount), None);
+    /// ```
+    pub fn transfer(mut self, mut to: Client, amount: Amount) -> Option<(Client, Client)> {
+        if self.locked || to.locked || self.available < amount {
+            return None;
+        }
+
+        self.available -= amount;
+        self.total = self.available + self.held;
+        to.available += amount;
+        to.total = to.available + to.held;
+
+        Some((self, to))
+    }
+
+    /// Move part of the held funds of a dispute back to available, the rest stays held.
+    pub fn resolve_part(mut self, amount: Amount) -> Self {
+        self.held -= amount;
+        self.available += amount;
+        self.total = self.available + self.held;
+
+        self
+    }
+
+    /// Settle a step of the dispute of a withdrawal of the amount, see [`ClientLedger::apply`].
+    /// The dispute holds the claimed amount, a resolve rejects the claim and the withdrawal stands,
+    /// a chargeback returns the amount to available and locks the client.
+    pub fn dispute_withdrawal(mut self, tx_type: TransactionType, amount: Amount) -> Self {
+        match tx_type {
+            TransactionType::Dispute => self.held += amount,
+            TransactionType::Resolve => self.held -= amount.min(self.held),
+            TransactionType::Chargeback => {
+                let charged: Amount = amount.min(self.held);
+                self.held -= charged;
+                self.available += charged;
+                self.locked = true;
+            }
+            _ => {}
+        }
+        self.total = self.available + self.held;
+
+        self
+    }
+
     /// Process All transactions and return the client with the balance.
     /// For no further information about client, this create a new one when a new ID is found.
     ///
     /// # Examples
     /// ```
-    /// let transactions: Vec<Transaction> = Transaction::get_transactions(path);
-    /// let clients: Vec<Client> = Client::process_transactions(transactions);
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// # let tx: String = String::from("type,client,tx,amount\ndeposit,1,1,1.0");
+    /// let transactions: Vec<Transaction> = Transaction::get_transactions(tx).unwrap();
+    /// let clients: Vec<Client> = Client::process_transactions(&transactions);
     /// ```
-    pub fn process_transactions(txs: &Vec<Transaction>) -> Vec<Client> {
-        let mut clients: Vec<Client> = Vec::new();
+    pub fn process_transactions(txs: &[Transaction]) -> Vec<Client> {
+        Client::process_transactions_with(txs, &Config::default())
+    }
 
-        for transaction in txs {
-            let client_id: u32 = transaction.client;
-
-            //If the client exists
-            match clients.iter().position(|c| c.client == client_id) {
-                Some(cl_index) => {
-                    if clients[cl_index].locked {
-                        continue;
-                    }
-
-                    //If the transaction is a dispute, the previos amount need to be found
-                    if transaction.is_dispute() {
-                        match Transaction::get_prev_trans(txs, transaction.tx) {
-                            Some(ori_tx_id) => {
-                                //If the previos tx exists, make the transaction.
-                                clients[cl_index] = clients[cl_index].new_transaction(
-                                    transaction.tx_type.clone(),
-                                    txs[ori_tx_id].amount,
-                                );
-                                continue;
-                            }
-                            _ => (),
-                        }
-                    }
-
-                    //If the transaction is not a dispute, the amount of tx is used
-                    clients[cl_index] = clients[cl_index]
-                        .new_transaction(transaction.tx_type.clone(), transaction.amount);
-                }
-                //If the user don't exists, create a new one and make the transaction.
-                None => {
-                    let mut new_client: Client = Client::new(client_id);
-                    new_client =
-                        new_client.new_transaction(transaction.tx_type.clone(), transaction.amount);
+    /// Same as [`Client::process_transactions`], but a deposit, withdrawal or transfer with a tx ID already
+    /// applied is skipped, like the same row in the CSVs of two days merged together. Disputes, resolves and
+    /// chargebacks reference the tx ID of another transaction, so they are always applied.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let deposit: Transaction = Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap());
+    /// let clients: Vec<Client> = Client::process_transactions_dedup(&[deposit.clone(), deposit]);
+    /// assert_eq!(clients[0].total(), "1.0".parse().unwrap());
+    /// ```
+    pub fn process_transactions_dedup(txs: &[Transaction]) -> Vec<Client> {
+        let mut applied: HashSet<u32> = HashSet::new();
+        let txs: Vec<Transaction> = txs
+            .iter()
+            .filter(|t| t.is_dispute() || applied.insert(t.tx))
+            .cloned()
+            .collect();
 
-                    clients.push(new_client);
-                }
-            }
+        Client::process_transactions(&txs)
+    }
+
+    /// Process All transactions as told by the [`Config`], like the `dispute_key` to find the disputed deposits.
+    /// The clients are indexed by ID in a [`ClientLedger`], so each transaction finds its client in constant time,
+    /// and they are returned in the order they were first seen.
+    ///
+    /// In debug builds this checks that there is one client for each distinct client ID of the transactions.
+    pub fn process_transactions_with(txs: &[Transaction], config: &Config) -> Vec<Client> {
+        Client::process_transactions_with_report(txs, config).0
+    }
+
+    /// Apply the transactions as they come from the iterator, like the one of [`Transaction::stream_from_path`],
+    /// and return the clients in the order they were first seen. The transactions are never held, so the
+    /// memory doesn't grow with the rows: the [`ClientLedger`] keeps the clients and, for the disputes that
+    /// reference them later, the tx ID and amount of every deposit and withdrawal. That part still grows
+    /// with the number of deposits, a dispute can reference any earlier one.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first [`TransactionError`] of the iterator, the transactions after it are not applied.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let transactions = [Ok(Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap()))];
+    /// let clients: Vec<Client> = Client::process_stream(transactions).unwrap();
+    /// assert_eq!(clients[0].total(), "1.0".parse().unwrap());
+    /// ```
+    pub fn process_stream(
+        transactions: impl IntoIterator<Item = Result<Transaction, TransactionError>>,
+    ) -> Result<Vec<Client>, TransactionError> {
+        let mut ledger: ClientLedger = ClientLedger::new();
+
+        for transaction in transactions {
+            ledger.apply(&transaction?);
         }
 
-        return clients;
+        Ok(ledger.into_clients())
+    }
+
+    /// Process All transactions like [`Client::process_transactions_with`], and return with the clients
+    /// the [`ProcessReport`] of what was skipped, like the transactions for locked clients,
+    /// and the withdrawals rejected because the client didn't have the funds.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::ledger::ProcessReport;
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::config::models::Config;
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// # let tx: String = String::from("type,client,tx,amount\ndeposit,1,1,1.0");
+    /// let transactions: Vec<Transaction> = Transaction::get_transactions(tx).unwrap();
+    /// let (clients, report): (Vec<Client>, ProcessReport) =
+    ///     Client::process_transactions_with_report(&transactions, &Config::default());
+    /// assert_eq!(report.skipped(), 0);
+    /// ```
+    pub fn process_transactions_with_report(
+        txs: &[Transaction],
+        config: &Config,
+    ) -> (Vec<Client>, ProcessReport) {
+        let mut ledger: ClientLedger = ClientLedger::with_config(config);
+
+        for transaction in txs {
+            ledger.apply(transaction);
+        }
+
+        let report: ProcessReport = ledger.report().clone();
+        //In debug builds into_clients checks that no client is dropped, added or duplicated
+        (ledger.into_clients(), report)
+    }
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "client {}: available={} held={} total={} locked={}",
+            self.client, self.available, self.held, self.total, self.locked
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::ledger::RejectedWithdrawal;
     use super::*;
 
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
     #[test]
     fn new_test() {
         let cl: Client = Client {
             client: 0,
-            available: 0.0,
-            held: 0.0,
-            total: 0.0,
+            available: Amount::ZERO,
+            held: Amount::ZERO,
+            total: Amount::ZERO,
             locked: false,
+            counts: TypeCounts::default(),
+            clamped: Amount::ZERO,
+            locked_reason: None,
+            locked_at_tx: None,
         };
         let new_cl: Client = Client::new(0);
         assert_eq!(cl, new_cl)
@@ -164,57 +576,312 @@ mod tests {
         let new_cl: Client = Client::new(0);
 
         assert_eq!(
-            new_cl.new_transaction("deposit".to_string(), 1.2).available,
-            1.2
+            new_cl
+                .new_transaction(TransactionType::Deposit, amount("1.2"))
+                .available,
+            amount("1.2")
         );
 
         let cl_withdraw: Client = Client::new(1);
         assert_eq!(
             cl_withdraw
-                .new_transaction("withdrawal".to_string(), 1.0)
+                .new_transaction(TransactionType::Withdrawal, amount("1.0"))
                 .available,
-            0.0
+            amount("0.0")
         );
 
         let cl_dispute: Client = Client::new(2);
         assert_eq!(
-            cl_dispute.new_transaction("dispute".to_string(), 1.0).held,
-            1.0
+            cl_dispute
+                .new_transaction(TransactionType::Dispute, amount("1.0"))
+                .held,
+            amount("1.0")
         );
 
         //In this part create a scenario when a resolve can happen:
         //A deposit
-        let mut cl_to_resolve: Client = cl_dispute.new_transaction("deposit".to_string(), 1.0);
+        let mut cl_to_resolve: Client =
+            cl_dispute.new_transaction(TransactionType::Deposit, amount("1.0"));
         //Then a dispute
-        cl_to_resolve = cl_to_resolve.new_transaction("dispute".to_string(), 1.0);
+        cl_to_resolve = cl_to_resolve.new_transaction(TransactionType::Dispute, amount("1.0"));
         //To finally test the resolve.
         assert_eq!(
             cl_to_resolve
-                .new_transaction("resolve".to_string(), 1.0)
+                .new_transaction(TransactionType::Resolve, amount("1.0"))
                 .available,
-            1.0
+            amount("1.0")
         );
 
         let cl_dispute_cb: Client = Client::new(3);
-        let cl_chargeback = cl_dispute_cb.new_transaction("dispute".to_string(), 1.0);
+        let cl_chargeback = cl_dispute_cb.new_transaction(TransactionType::Dispute, amount("1.0"));
         assert_eq!(
             cl_chargeback
-                .new_transaction("chargeback".to_string(), 1.0)
+                .new_transaction(TransactionType::Chargeback, amount("1.0"))
                 .held,
-            0.0
+            amount("0.0")
         );
 
-        assert_eq!(
+        assert!(
             cl_chargeback
-                .new_transaction("chargeback".to_string(), 1.0)
-                .locked,
-            true
+                .new_transaction(TransactionType::Chargeback, amount("1.0"))
+                .locked
+        );
+    }
+
+    #[test]
+    fn balances_test() {
+        let client: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("5.0"))
+            .new_transaction(TransactionType::Dispute, amount("2.0"));
+
+        assert_eq!(
+            client.balances(),
+            Balances {
+                available: client.available,
+                held: client.held,
+                total: client.total,
+            }
+        );
+        assert_eq!(client.balances().available, amount("3.0"));
+        assert_eq!(client.balances().held, amount("2.0"));
+        assert_eq!(client.balances().total, amount("5.0"));
+        assert_eq!(Client::new(2).balances(), Balances::default());
+    }
+
+    #[test]
+    fn withdrawal_test() {
+        let client: Client =
+            Client::new(1).new_transaction(TransactionType::Deposit, amount("1.0"));
+        let withdraw = |value: &str| -> Amount {
+            client
+                .new_transaction(TransactionType::Withdrawal, amount(value))
+                .available
+        };
+
+        assert_eq!(withdraw("1.0"), amount("0.0"));
+        assert_eq!(withdraw("0.9999"), amount("0.0001"));
+        assert_eq!(withdraw("1.0001"), amount("1.0"));
+    }
+
+    #[test]
+    fn concurrent_resolve_test() {
+        let client: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("10.0"))
+            .new_transaction(TransactionType::Deposit, amount("20.0"))
+            .new_transaction(TransactionType::Dispute, amount("10.0"))
+            .new_transaction(TransactionType::Dispute, amount("20.0"));
+        assert_eq!(client.held, amount("30.0"));
+
+        //Only the resolved amount goes back, the other dispute stays held
+        let client: Client = client.new_transaction(TransactionType::Resolve, amount("10.0"));
+        assert_eq!(client.available, amount("10.0"));
+        assert_eq!(client.held, amount("20.0"));
+        assert_eq!(client.total, amount("30.0"));
+    }
+
+    #[test]
+    fn withdraw_test() {
+        let client: Client =
+            Client::new(1).new_transaction(TransactionType::Deposit, amount("5.0"));
+
+        //Withdrawing exactly the available amount is allowed
+        let withdrawn: Client = client.withdraw(amount("5.0")).unwrap();
+        assert_eq!(withdrawn.available, amount("0.0"));
+        assert_eq!(withdrawn.total, amount("0.0"));
+
+        //One cent more is rejected and the client keeps its funds
+        assert_eq!(client.withdraw(amount("5.01")), None);
+        assert_eq!(
+            client
+                .new_transaction(TransactionType::Withdrawal, amount("5.01"))
+                .available,
+            amount("5.0")
+        );
+    }
+
+    #[test]
+    fn chargeback_over_held_test() {
+        let client: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("10.0"))
+            .new_transaction(TransactionType::Dispute, amount("4.0"));
+
+        //A chargeback of more than held takes only the held funds, the account still locks
+        let charged_back: Client =
+            client.new_transaction(TransactionType::Chargeback, amount("9.0"));
+        assert_eq!(charged_back.held, amount("0.0"));
+        assert_eq!(charged_back.available, amount("6.0"));
+        assert_eq!(charged_back.total, amount("6.0"));
+        assert!(charged_back.locked);
+
+        let withdrawn: Client =
+            client.dispute_withdrawal(TransactionType::Chargeback, amount("9.0"));
+        assert_eq!(withdrawn.held, amount("0.0"));
+        assert_eq!(withdrawn.available, amount("10.0"));
+        assert!(withdrawn.locked);
+    }
+
+    #[test]
+    fn resolve_over_held_test() {
+        //A resolve without a dispute frees nothing
+        let client: Client =
+            Client::new(1).new_transaction(TransactionType::Resolve, amount("1.0"));
+        assert_eq!(client.held, Amount::ZERO);
+        assert_eq!(client.available, Amount::ZERO);
+
+        let client: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("10.0"))
+            .new_transaction(TransactionType::Dispute, amount("4.0"));
+        let resolved: Client = client.new_transaction(TransactionType::Resolve, amount("9.0"));
+        assert_eq!(resolved.held, amount("0.0"));
+        assert_eq!(resolved.available, amount("10.0"));
+        assert_eq!(resolved.total, amount("10.0"));
+
+        let withdrawn: Client = client.dispute_withdrawal(TransactionType::Resolve, amount("9.0"));
+        assert_eq!(withdrawn.held, amount("0.0"));
+        assert_eq!(withdrawn.available, amount("6.0"));
+        assert!(!withdrawn.locked);
+    }
+
+    #[test]
+    fn display_test() {
+        let client: Client = Client::new(5)
+            .new_transaction(TransactionType::Deposit, amount("12.5"))
+            .new_transaction(TransactionType::Dispute, amount("2.5"))
+            .new_transaction(TransactionType::Chargeback, amount("2.5"));
+
+        assert_eq!(
+            client.to_string(),
+            "client 5: available=10.0000 held=0.0000 total=10.0000 locked=true"
+        );
+        assert_eq!(
+            Client::from_balances(5, amount("10.0"), amount("2.5"), true).to_string(),
+            "client 5: available=10.0000 held=2.5000 total=12.5000 locked=true"
+        );
+        assert_eq!(
+            Client::new(7).to_string(),
+            "client 7: available=0.0000 held=0.0000 total=0.0000 locked=false"
+        );
+    }
+
+    #[test]
+    fn fee_test() {
+        let client: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("1.0"))
+            .new_transaction(TransactionType::Fee, amount("1.5"));
+
+        //Unlike a withdrawal, the fee is taken without the funds
+        assert_eq!(client.available, amount("-0.5"));
+        assert_eq!(client.total, amount("-0.5"));
+        assert_eq!(client.held, amount("0.0"));
+    }
+
+    #[test]
+    fn merge_test() {
+        let first: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("10.0"))
+            .new_transaction(TransactionType::Dispute, amount("4.0"));
+        let second: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("3.0"))
+            .new_transaction(TransactionType::Dispute, amount("3.0"))
+            .new_transaction(TransactionType::Chargeback, amount("3.0"));
+        assert!(!first.locked && second.locked);
+
+        let merged: Client = first.merge(second);
+        assert_eq!(merged.client, 1);
+        assert_eq!(merged.available, first.available + second.available);
+        assert_eq!(merged.held, amount("4.0"));
+        assert_eq!(merged.total, amount("10.0"));
+        assert!(merged.locked);
+        assert_eq!(merged.locked_reason, second.locked_reason);
+
+        //The lock is kept whichever partial it comes from
+        assert!(second.merge(first).locked);
+        assert!(!first.merge(Client::new(1)).locked);
+    }
+
+    #[test]
+    fn from_balances_test() {
+        let client: Client = Client::new(7)
+            .new_transaction(TransactionType::Deposit, amount("10.0"))
+            .new_transaction(TransactionType::Deposit, amount("2.5"))
+            .new_transaction(TransactionType::Dispute, amount("2.5"))
+            .new_transaction(TransactionType::Chargeback, amount("2.5"));
+        let restored: Client = Client::from_balances(
+            client.id(),
+            client.available(),
+            client.held(),
+            client.is_locked(),
+        );
+        assert!(restored.same_balance(&client));
+        assert_eq!(restored.balances(), client.balances());
+        assert_eq!(restored.locked_reason(), None);
+
+        let client: Client = Client::from_balances(2, amount("-1.0"), amount("3.0"), false);
+        assert_eq!(client.total, amount("2.0"));
+        assert!(!client.locked);
+
+        //A restored client keeps taking transactions
+        let client: Client = client.new_transaction(TransactionType::Deposit, amount("1.0"));
+        assert_eq!(client.available, Amount::ZERO);
+        assert_eq!(client.total, amount("3.0"));
+    }
+
+    #[test]
+    fn accessors_test() {
+        let txs: Vec<Transaction> = vec![
+            Transaction::new(TransactionType::Deposit, 1, 1, amount("10.0")),
+            Transaction::new(TransactionType::Deposit, 1, 2, amount("2.5")),
+            Transaction::new(TransactionType::Dispute, 1, 2, amount("0.0")),
+            Transaction::new(TransactionType::Deposit, 2, 3, amount("1.0")),
+            Transaction::new(TransactionType::Dispute, 2, 3, amount("0.0")),
+            Transaction::new(TransactionType::Chargeback, 2, 3, amount("0.0")),
+        ];
+        let clients: Vec<Client> = Client::process_transactions(&txs);
+
+        //A library user reads the balances through the accessors only
+        let read = |client: &Client| {
+            (
+                client.id(),
+                client.available(),
+                client.held(),
+                client.total(),
+                client.is_locked(),
+            )
+        };
+        assert_eq!(
+            read(&clients[0]),
+            (1, amount("10.0"), amount("2.5"), amount("12.5"), false)
         );
+        assert_eq!(
+            read(&clients[1]),
+            (2, amount("0.0"), amount("0.0"), amount("0.0"), true)
+        );
+    }
+
+    #[test]
+    fn process_transactions_dedup_test() {
+        let deposit: Transaction = Transaction::new(TransactionType::Deposit, 1, 1, amount("5.0"));
+        let txs: Vec<Transaction> = vec![
+            deposit.clone(),
+            Transaction::new(TransactionType::Withdrawal, 1, 2, amount("1.0")),
+            //The second day repeats the deposit, and disputes it
+            deposit.clone(),
+            Transaction::new(TransactionType::Withdrawal, 1, 2, amount("1.0")),
+            Transaction::new(TransactionType::Dispute, 1, 1, amount("0.0")),
+        ];
+
+        let clients: Vec<Client> = Client::process_transactions_dedup(&txs);
+        assert_eq!(clients[0].available, amount("-1.0"));
+        assert_eq!(clients[0].held, amount("5.0"));
+        assert_eq!(clients[0].total, amount("4.0"));
+
+        //Without the guard the deposit is applied twice
+        assert_eq!(Client::process_transactions(&txs)[0].total, amount("8.0"));
     }
 
     #[test]
     fn process_transactions_test() {
-        let tx: Transaction = Transaction::new("deposit".to_string(), 1, 1, 1.0);
+        let tx: Transaction = Transaction::new(TransactionType::Deposit, 1, 1, amount("1.0"));
         let txs: Vec<Transaction> = vec![tx];
 
         let new_cl: Client = Client::new(1);
@@ -224,16 +891,220 @@ mod tests {
         assert_eq!(clients[0].client, new_cl.client)
     }
 
+    #[test]
+    fn process_stream_test() {
+        let path = std::env::temp_dir().join("accounts-cli-process-stream.csv");
+        std::fs::write(
+            &path,
+            "type, client, tx, amount\ndeposit, 2, 1, 4.0\ndeposit, 1, 2, 3.0\ndispute, 2, 1\nwithdrawal, 1, 3, 1.0\n",
+        )
+        .unwrap();
+
+        let clients: Vec<Client> =
+            Client::process_stream(Transaction::stream_from_path(path.to_str().unwrap()).unwrap())
+                .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let balances: Vec<(u32, Amount, Amount)> = clients
+            .iter()
+            .map(|c| (c.client, c.available, c.held))
+            .collect();
+        assert_eq!(
+            balances,
+            [
+                (2, amount("0.0"), amount("4.0")),
+                (1, amount("2.0"), amount("0.0"))
+            ]
+        );
+
+        //A bad row stops the stream with its line
+        let bad: [Result<Transaction, TransactionError>; 2] = [
+            Ok(Transaction::new(
+                TransactionType::Deposit,
+                1,
+                1,
+                amount("1.0"),
+            )),
+            Err(TransactionError::InvalidTx {
+                line: 3,
+                value: "two".to_string(),
+            }),
+        ];
+        assert!(Client::process_stream(bad).is_err());
+        assert!(Transaction::stream_from_path("accounts-cli-missing.csv").is_err());
+    }
+
+    #[test]
+    fn process_report_test() {
+        let txs: Vec<Transaction> = vec![
+            Transaction::new(TransactionType::Deposit, 1, 1, amount("5.0")),
+            Transaction::new(TransactionType::Dispute, 1, 1, amount("0.0")),
+            Transaction::new(TransactionType::Chargeback, 1, 1, amount("0.0")),
+            Transaction::new(TransactionType::Deposit, 1, 2, amount("1.0")),
+            Transaction::new(TransactionType::Deposit, 1, 3, amount("2.0")),
+            Transaction::new(TransactionType::Deposit, 2, 4, amount("1.0")),
+        ];
+
+        let (clients, report): (Vec<Client>, ProcessReport) =
+            Client::process_transactions_with_report(&txs, &Config::default());
+        assert_eq!(clients, Client::process_transactions(&txs));
+        assert_eq!(clients[0].total, amount("0.0"));
+        assert_eq!(report.skipped(), 2);
+        assert_eq!(report.skipped_locked, [(1, 2)].into());
+    }
+
+    #[test]
+    fn rejected_withdrawal_test() {
+        let txs: Vec<Transaction> = vec![
+            Transaction::new(TransactionType::Deposit, 1, 1, amount("5.0")),
+            Transaction::new(TransactionType::Withdrawal, 1, 2, amount("2.0")),
+            Transaction::new(TransactionType::Withdrawal, 1, 3, amount("3.5")),
+            Transaction::new(TransactionType::Withdrawal, 1, 4, amount("3.0")),
+        ];
+
+        let (clients, report): (Vec<Client>, ProcessReport) =
+            Client::process_transactions_with_report(&txs, &Config::default());
+        assert_eq!(clients[0].available, amount("0.0"));
+        assert_eq!(
+            report.rejected_withdrawals,
+            [RejectedWithdrawal {
+                client: 1,
+                tx: 3,
+                requested: amount("3.5"),
+                available: amount("3.0"),
+            }]
+        );
+    }
+
+    #[test]
+    fn counterparty_of_deposit_test() {
+        let tx: String =
+            String::from("type, client, tx, amount, counterparty\ndeposit, 1, 1, 1.0, 2");
+        let txs: Vec<Transaction> = Transaction::get_transactions(tx).unwrap();
+        assert_eq!(txs[0].counterparty, Some(2));
+
+        //The counterparty of a deposit is not a client
+        let clients: Vec<Client> = Client::process_transactions(&txs);
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].client, 1);
+        assert_eq!(clients[0].total, amount("1.0"));
+        let (clients, _) = Client::process_transactions_with_report(&txs, &Config::default());
+        assert_eq!(clients.len(), 1);
+    }
+
+    #[test]
+    fn client_count_test() {
+        let txs: Vec<Transaction> = (0..300)
+            .map(|id| Transaction::new(TransactionType::Deposit, id % 7, id, amount("1.0")))
+            .collect();
+
+        let clients: Vec<Client> = Client::process_transactions(&txs);
+        let ids: HashSet<u32> = clients.iter().map(|c| c.client).collect();
+
+        assert_eq!(clients.len(), 7);
+        assert_eq!(ids, (0..7).collect::<HashSet<u32>>());
+    }
+
+    #[test]
+    fn many_clients_test() {
+        let txs: Vec<Transaction> = (0..100_000)
+            .map(|id| Transaction::new(TransactionType::Deposit, id % 50_000, id, amount("1.0")))
+            .collect();
+
+        //A linear search of the clients would take minutes here
+        let start: std::time::Instant = std::time::Instant::now();
+        let clients: Vec<Client> = Client::process_transactions(&txs);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        assert_eq!(clients.len(), 50_000);
+        assert_eq!(clients[0].client, 0);
+        assert_eq!(clients[49_999].total, amount("2.0"));
+    }
+
+    #[test]
+    fn grand_total_test() {
+        let mut clients: Vec<Client> = Vec::new();
+        for id in 0..3 {
+            let mut client: Client = Client::new(id);
+            client = client.new_transaction(TransactionType::Deposit, amount("1.5"));
+            clients.push(client);
+        }
+        assert_eq!(Client::grand_total(&clients), Some(amount("4.5")));
+
+        //Each balance fits in an Amount but the sum doesn't
+        let large: Vec<Client> = (0..100)
+            .map(|id| Client {
+                client: id,
+                available: Amount::from_scaled(i64::MAX / 4),
+                held: Amount::ZERO,
+                total: Amount::from_scaled(i64::MAX / 4),
+                locked: false,
+                counts: TypeCounts::default(),
+                clamped: Amount::ZERO,
+                locked_reason: None,
+                locked_at_tx: None,
+            })
+            .collect();
+
+        assert_eq!(Client::grand_total(&large), None);
+    }
+
+    #[test]
+    fn no_drift_test() {
+        let txs: Vec<Transaction> = (0..10_000)
+            .map(|id| Transaction::new(TransactionType::Deposit, 1, id, amount("0.0001")))
+            .collect();
+
+        let clients: Vec<Client> = Client::process_transactions(&txs);
+        assert_eq!(clients[0].total, amount("1.0"));
+        assert_eq!(clients[0].available, Amount::from_scaled(Amount::SCALE));
+        assert_eq!(
+            Client::clients_to_csv(clients).unwrap(),
+            "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n"
+        );
+    }
+
     #[test]
     fn clients_csv_test() {
         let client = Client::new(1);
         let clients: Vec<Client> = vec![client];
 
-        let cl_string: String = Client::clients_to_csv(clients);
+        let cl_string: String = Client::clients_to_csv(clients).unwrap();
 
         let clients_string: String =
-            String::from("client,available,held,total,locked\n1,0.0,0.0,0.0,false\n");
+            String::from("client,available,held,total,locked\n1,0.0000,0.0000,0.0000,false\n");
 
         assert_eq!(cl_string, clients_string)
     }
+
+    #[test]
+    fn four_decimals_test() {
+        //0.1 + 0.2 is 0.30000001 in f32 and 0.30000000000000004 in f64
+        let client: Client = ["0.1", "0.2", "0.1", "0.2", "0.1", "0.2"]
+            .into_iter()
+            .fold(Client::new(1), |client, value| {
+                client.new_transaction(TransactionType::Deposit, amount(value))
+            })
+            .new_transaction(TransactionType::Withdrawal, amount("0.6"));
+
+        assert_eq!(
+            Client::clients_to_csv(vec![client]).unwrap(),
+            "client,available,held,total,locked\n1,0.3000,0.0000,0.3000,false\n"
+        );
+    }
+
+    #[test]
+    fn sorted_csv_test() {
+        let txs: Vec<Transaction> = [3, 1, 2]
+            .into_iter()
+            .map(|id| Transaction::new(TransactionType::Deposit, id, id, amount("1.0")))
+            .collect();
+        let clients: Vec<Client> = Client::process_transactions(&txs);
+        assert_eq!(clients[0].client, 3);
+
+        assert_eq!(
+            Client::clients_to_csv(clients).unwrap(),
+            "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n2,1.0000,0.0000,1.0000,false\n3,1.0000,0.0000,1.0000,false\n"
+        );
+    }
 }
@@ -0,0 +1,1149 @@
+use super::super::config::models::{ClientOrder, Config, OutputFormat};
+use super::super::money::{currency_decimals, Amount};
+use super::ledger::ClientLedger;
+use super::models::Client;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Read, Write};
+
+impl Client {
+    /// Serialize the users as CSV straight into the writer, one row at a time.
+    /// The optional columns of the [`Config`] are added after the balances.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`csv::Error`] if a client can't be serialized or written.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::config::models::Config;
+    /// let clients: Vec<Client> = vec![Client::new(1)];
+    /// Client::clients_to_writer(clients, std::io::stdout(), &Config::default()).unwrap();
+    /// ```
+    pub fn clients_to_writer<W: Write>(
+        clients: impl IntoIterator<Item = Client>,
+        writer: W,
+        config: &Config,
+    ) -> Result<(), csv::Error> {
+        let mut clients_csv: csv::Writer<W> = csv::Writer::from_writer(writer);
+        let mut empty: bool = true;
+
+        for client in clients {
+            clients_csv.serialize(ClientRow {
+                client: &client,
+                config,
+                held: None,
+                currency: None,
+            })?;
+            empty = false;
+        }
+
+        //Without clients the header is still written
+        if empty {
+            clients_csv.write_record(ClientRow::header(config))?;
+        }
+
+        clients_csv.flush()?;
+        Ok(())
+    }
+
+    /// Read back the clients of a CSV written by [`Client::clients_to_writer`], like the opening
+    /// balances of a run with `--opening`. The columns are found by name, so the optional ones are
+    /// left out, and a locked client stays locked.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`csv::Error`] if a row can't be read, if its total is not the available and held funds together,
+    /// or if its client ID is repeated, like in the output of `--split-currencies` with a row by currency.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// let output: &str = "client,available,held,total,locked\n1,1.5000,0.5000,2.0000,true\n";
+    /// let clients: Vec<Client> = Client::clients_from_reader(output.as_bytes()).unwrap();
+    /// assert!(clients[0].is_locked());
+    /// ```
+    pub fn clients_from_reader<R: Read>(reader: R) -> Result<Vec<Client>, csv::Error> {
+        let mut reader: csv::Reader<R> = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let mut clients: Vec<Client> = Vec::new();
+        let mut ids: HashSet<u32> = HashSet::new();
+        let invalid =
+            |message: String| csv::Error::from(io::Error::new(io::ErrorKind::InvalidData, message));
+
+        for (row, record) in reader.deserialize::<OpeningRow>().enumerate() {
+            let record: OpeningRow = record?;
+            let line: usize = row + 2;
+            let client: Client =
+                Client::from_balances(record.client, record.available, record.held, record.locked);
+            if client.total != record.total {
+                return Err(invalid(format!(
+                    "Line {}: total {} of client {} is not {}",
+                    line, record.total, record.client, client.total
+                )));
+            }
+            if !ids.insert(record.client) {
+                return Err(invalid(format!(
+                    "Line {}: client {} is repeated",
+                    line, record.client
+                )));
+            }
+            clients.push(client);
+        }
+
+        Ok(clients)
+    }
+
+    /// Returns the users as a JSON array, indented if `pretty`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`io::Error`] if a client can't be serialized, see [`Client::write_clients`].
+    pub fn clients_to_json(clients: Vec<Client>, pretty: bool) -> Result<String, io::Error> {
+        let config: Config = Config {
+            format: OutputFormat::Json,
+            pretty,
+            ..Config::default()
+        };
+        let mut data: Vec<u8> = Vec::new();
+
+        Client::write_clients(clients, &mut data, &config)?;
+
+        String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Serialize the users into the writer in the `format` of the [`Config`].
+    /// The JSON formats are compact unless `pretty` is set, and end with a new line.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a client can't be serialized or written.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::config::models::{Config, OutputFormat};
+    /// let config: Config = Config {
+    ///     format: OutputFormat::JsonMap,
+    ///     ..Config::default()
+    /// };
+    /// let mut data: Vec<u8> = Vec::new();
+    ///
+    /// Client::write_clients(vec![Client::new(1)], &mut data, &config).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(data).unwrap(),
+    ///     "{\"1\":{\"client\":1,\"available\":0.0000,\"held\":0.0000,\"total\":0.0000,\"locked\":false}}\n"
+    /// );
+    /// ```
+    pub fn write_clients<W: Write>(
+        clients: Vec<Client>,
+        writer: W,
+        config: &Config,
+    ) -> io::Result<()> {
+        let rows: Vec<ClientRow> = clients
+            .iter()
+            .map(|client| ClientRow {
+                client,
+                config,
+                held: None,
+                currency: None,
+            })
+            .collect();
+
+        write_rows(&rows, writer, config)
+    }
+}
+
+impl ClientLedger {
+    /// Serialize the clients into the writer like [`Client::write_clients`], by ID or in the order they were
+    /// first seen, as told by the `client_order` of the [`Config`].
+    /// With `held_breakdown` each JSON client also has the held amount of its open disputes, by tx ID.
+    /// With `changed_only` the clients that still have the balances they were seeded with are left out.
+    /// The amounts of a client with a known currency are written with the decimal places of the currency.
+    /// With `split_currencies` the balances of each currency follow with a `currency` column, by its code.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::Error`] if a client can't be serialized or written.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::clients::ledger::ClientLedger;
+    /// # use accounts_cli::config::models::Config;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let mut ledger: ClientLedger = ClientLedger::new();
+    /// ledger.apply(&Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap()));
+    /// ledger.write_clients(std::io::stdout(), &Config::default()).unwrap();
+    /// ```
+    pub fn write_clients<W: Write>(mut self, writer: W, config: &Config) -> io::Result<()> {
+        let held: HeldByClient = std::mem::take(&mut self.held);
+        let opening: HashMap<u32, Client> = std::mem::take(&mut self.opening);
+        let currencies: HashMap<u32, String> = std::mem::take(&mut self.currencies);
+        let split: Vec<(String, HeldByClient, Vec<Client>)> = std::mem::take(&mut self.split)
+            .into_iter()
+            .map(|(code, mut ledger)| {
+                let held: HeldByClient = std::mem::take(&mut ledger.held);
+                (code, held, ledger.into_clients())
+            })
+            .collect();
+        let mut clients: Vec<Client> = self.into_clients();
+
+        if config.changed_only {
+            clients.retain(|client| {
+                !opening
+                    .get(&client.client)
+                    .is_some_and(|seeded| seeded.same_balance(client))
+            });
+        }
+
+        let mut rows: Vec<ClientRow> = clients
+            .iter()
+            .map(|client| ClientRow {
+                client,
+                config,
+                held: held.get(&client.client),
+                currency: currencies.get(&client.client).map(|c| c.as_str()),
+            })
+            .collect();
+        for (code, held, clients) in &split {
+            rows.extend(clients.iter().map(|client| ClientRow {
+                client,
+                config,
+                held: held.get(&client.client),
+                currency: Some(code.as_str()),
+            }));
+        }
+        //The balances of each currency of a client follow each other, by code
+        if config.client_order == ClientOrder::ClientId {
+            rows.sort_by_key(|row| (row.client.client, row.currency));
+        }
+
+        write_rows(&rows, writer, config)
+    }
+}
+
+/// Held amount of the open disputes of each client, by tx ID.
+type HeldByClient = HashMap<u32, BTreeMap<u32, Amount>>;
+
+/// Serialize the rows into the writer in the `format` of the [`Config`].
+/// With `with_totals` the CSV ends with a `total` row, the JSON lines with a `totals` object
+/// and the other JSON formats are an object with the `clients` and their `totals`.
+fn write_rows<W: Write>(rows: &[ClientRow], mut writer: W, config: &Config) -> io::Result<()> {
+    let totals: Option<Totals> = config.with_totals.then(|| Totals::new(rows));
+
+    match config.format {
+        OutputFormat::Csv => {
+            let mut clients_csv: csv::Writer<W> = csv::Writer::from_writer(writer);
+            for row in rows {
+                clients_csv.serialize(row)?;
+            }
+
+            //Without clients the header is still written
+            if rows.is_empty() {
+                clients_csv.write_record(ClientRow::header(config))?;
+            }
+            if let Some(totals) = totals {
+                clients_csv.write_record(totals.record(config))?;
+            }
+            return clients_csv.flush();
+        }
+        //Each client is written as soon as it's serialized, a line at a time
+        OutputFormat::JsonLines => {
+            for row in rows {
+                serde_json::to_writer(&mut writer, row)?;
+                writeln!(writer)?;
+            }
+            if let Some(totals) = totals {
+                serde_json::to_writer(&mut writer, &TotalsLine { totals })?;
+                writeln!(writer)?;
+            }
+            return writer.flush();
+        }
+        OutputFormat::Json => write_json(&mut writer, &rows, totals, config.pretty)?,
+        OutputFormat::JsonMap => write_json(&mut writer, &ClientMap(rows), totals, config.pretty)?,
+    }
+
+    writeln!(writer)?;
+    writer.flush()
+}
+
+/// Serialize the clients as JSON, in an object with the totals if there are, indented if `pretty`.
+fn write_json<W: Write, C: Serialize>(
+    writer: &mut W,
+    clients: &C,
+    totals: Option<Totals>,
+    pretty: bool,
+) -> serde_json::Result<()> {
+    match (totals, pretty) {
+        (Some(totals), true) => {
+            serde_json::to_writer_pretty(writer, &WithTotals { clients, totals })
+        }
+        (Some(totals), false) => serde_json::to_writer(writer, &WithTotals { clients, totals }),
+        (None, true) => serde_json::to_writer_pretty(writer, clients),
+        (None, false) => serde_json::to_writer(writer, clients),
+    }
+}
+
+/// Sums of the balances of the clients written, whatever their currency, and how many are locked.
+/// Only the JSON formats serialize it, the CSV row is its [`Totals::record`].
+#[derive(Debug, Serialize)]
+struct Totals {
+    #[serde(serialize_with = "json_number")]
+    available: Amount,
+    #[serde(serialize_with = "json_number")]
+    held: Amount,
+    #[serde(serialize_with = "json_number")]
+    total: Amount,
+    locked: usize,
+}
+
+/// An amount written as a JSON number with the digits of its text, like `1.5000`, so it's never
+/// rounded through a float.
+#[derive(Debug)]
+struct JsonNumber(String);
+
+impl Serialize for JsonNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_json::value::RawValue::from_string(self.0.clone())
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+/// Serialize an amount of the JSON output as a number, see [`JsonNumber`].
+fn json_number<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+    JsonNumber(amount.to_string()).serialize(serializer)
+}
+
+impl Totals {
+    fn new(rows: &[ClientRow]) -> Totals {
+        let mut totals: Totals = Totals {
+            available: Amount::ZERO,
+            held: Amount::ZERO,
+            total: Amount::ZERO,
+            locked: 0,
+        };
+        for row in rows {
+            totals.available += row.client.available;
+            totals.held += row.client.held;
+            totals.total += row.client.total;
+            totals.locked += usize::from(row.client.locked);
+        }
+
+        totals
+    }
+
+    /// The CSV row of the totals, `total` in the client column and the number of locked clients in
+    /// the locked one. The other columns of the header are empty.
+    fn record(&self, config: &Config) -> Vec<String> {
+        ClientRow::header(config)
+            .into_iter()
+            .map(|column| match column {
+                "client" => "total".to_string(),
+                "available" => self.available.to_string(),
+                "held" => self.held.to_string(),
+                "total" => self.total.to_string(),
+                "locked" => self.locked.to_string(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+}
+
+/// The last line of the JSON lines with `with_totals`, apart from the client objects.
+#[derive(Serialize)]
+struct TotalsLine {
+    totals: Totals,
+}
+
+/// The JSON output with `with_totals`, the clients in their format and the totals apart.
+#[derive(Serialize)]
+struct WithTotals<'a, C: Serialize> {
+    clients: &'a C,
+    totals: Totals,
+}
+
+/// A client as written in the output, with the columns chosen in the [`Config`].
+struct ClientRow<'a> {
+    client: &'a Client,
+    config: &'a Config,
+    /// Held amount of the open disputes, by tx ID.
+    held: Option<&'a BTreeMap<u32, Amount>>,
+    /// Currency of the client, it chooses the decimal places of the amounts.
+    currency: Option<&'a str>,
+}
+
+impl ClientRow<'_> {
+    /// Names of the CSV columns chosen in the [`Config`], in the order they are serialized.
+    fn header(config: &Config) -> Vec<&'static str> {
+        let mut header: Vec<&'static str> = vec!["client"];
+        if config.split_currencies {
+            header.push("currency");
+        }
+        header.extend(["available", "held", "total", "locked"]);
+
+        if config.with_type_counts {
+            header.extend([
+                "deposits",
+                "withdrawals",
+                "disputes",
+                "resolves",
+                "chargebacks",
+            ]);
+        }
+        if config.clamp_negative_total {
+            header.push("clamped");
+        }
+        if config.with_locked_reason {
+            header.extend(["locked_reason", "locked_at_tx"]);
+        }
+
+        header
+    }
+
+    /// Returns the client ID as written in the output, zero-padded if `pad_client_id` is set.
+    fn client_id(&self) -> String {
+        let width: usize = self.config.pad_client_id.unwrap_or(0);
+        format!("{:0width$}", self.client.client, width = width)
+    }
+}
+
+impl ClientRow<'_> {
+    /// Serialize an amount column with the decimal places of `output_precision`, else the ones of the
+    /// currency of the client, or four without them. In JSON the amount is a number. With
+    /// `accounting_negatives` a negative amount is written in parentheses, as a string in JSON.
+    fn serialize_amount<S: SerializeStruct>(
+        &self,
+        row: &mut S,
+        field: &'static str,
+        amount: Amount,
+    ) -> Result<(), S::Error> {
+        let decimals: Option<usize> = self
+            .config
+            .output_precision
+            .get(field)
+            .copied()
+            .or_else(|| self.currency.and_then(currency_decimals));
+
+        let text: String = match decimals {
+            Some(decimals) => amount.to_string_with(decimals),
+            None => amount.to_string(),
+        };
+
+        match text.strip_prefix('-') {
+            Some(digits) if self.config.accounting_negatives => {
+                row.serialize_field(field, &format!("({})", digits))
+            }
+            _ if self.config.format != OutputFormat::Csv => {
+                row.serialize_field(field, &JsonNumber(text))
+            }
+            _ => row.serialize_field(field, &text),
+        }
+    }
+}
+
+impl Serialize for ClientRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let client: &Client = self.client;
+        let mut row = serializer.serialize_struct("Client", 14)?;
+
+        //Padded IDs are text, so the zeros are kept in JSON too
+        match self.config.pad_client_id {
+            Some(_) => row.serialize_field("client", &self.client_id())?,
+            None => row.serialize_field("client", &client.client)?,
+        }
+        if self.config.split_currencies {
+            row.serialize_field("currency", self.currency.unwrap_or(""))?;
+        }
+        self.serialize_amount(&mut row, "available", client.available)?;
+        self.serialize_amount(&mut row, "held", client.held)?;
+        self.serialize_amount(&mut row, "total", client.total)?;
+        row.serialize_field("locked", &client.locked)?;
+
+        if self.config.with_type_counts {
+            row.serialize_field("deposits", &client.counts.deposits)?;
+            row.serialize_field("withdrawals", &client.counts.withdrawals)?;
+            row.serialize_field("disputes", &client.counts.disputes)?;
+            row.serialize_field("resolves", &client.counts.resolves)?;
+            row.serialize_field("chargebacks", &client.counts.chargebacks)?;
+        }
+
+        if self.config.clamp_negative_total {
+            self.serialize_amount(&mut row, "clamped", client.clamped)?;
+        }
+
+        if self.config.with_locked_reason {
+            row.serialize_field("locked_reason", &client.locked_reason)?;
+            row.serialize_field("locked_at_tx", &client.locked_at_tx)?;
+        }
+
+        //The breakdown is JSON only, Config::from_args rejects it with CSV
+        if self.config.held_breakdown {
+            let held: BTreeMap<u32, JsonNumber> = self
+                .held
+                .into_iter()
+                .flatten()
+                .map(|(tx, amount)| (*tx, JsonNumber(amount.to_string())))
+                .collect();
+            row.serialize_field("held_by_dispute", &held)?;
+        }
+
+        row.end()
+    }
+}
+
+/// Serialize the clients as a JSON object by ID, keeping their order.
+struct ClientMap<'a>(&'a [ClientRow<'a>]);
+
+impl Serialize for ClientMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|row| (row.client_id(), row)))
+    }
+}
+
+/// Columns of a client read back by [`Client::clients_from_reader`].
+#[derive(Deserialize)]
+struct OpeningRow {
+    client: u32,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::transactions::models::{Transaction, TransactionType};
+    use super::super::ledger::ClientLedger;
+    use super::super::models::LockReason;
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn clients_from_reader_test() {
+        let clients: Vec<Client> = vec![
+            Client::new(1)
+                .new_transaction(TransactionType::Deposit, amount("10.0"))
+                .new_transaction(TransactionType::Dispute, amount("2.5")),
+            Client::new(2)
+                .new_transaction(TransactionType::Deposit, amount("3.0"))
+                .new_transaction(TransactionType::Dispute, amount("3.0"))
+                .new_transaction(TransactionType::Chargeback, amount("3.0")),
+        ];
+        let config: Config = Config {
+            with_type_counts: true,
+            ..Config::default()
+        };
+        let mut output: Vec<u8> = Vec::new();
+        Client::clients_to_writer(clients.clone(), &mut output, &config).unwrap();
+
+        let read: Vec<Client> = Client::clients_from_reader(output.as_slice()).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(read.iter().zip(&clients).all(|(a, b)| a.same_balance(b)));
+        assert!(read[1].locked);
+
+        //Only the header gives no clients
+        assert_eq!(
+            Client::clients_from_reader("client,available,held,total,locked\n".as_bytes()).unwrap(),
+            []
+        );
+        for output in [
+            "client,available,held,total,locked\n1,1.0,1.0,3.0,false\n",
+            "client,available,held,total\n1,1.0,1.0,2.0\n",
+            "client,available,held,total,locked\ntotal,1.0,1.0,2.0,0\n",
+        ] {
+            assert!(Client::clients_from_reader(output.as_bytes()).is_err());
+        }
+
+        //A client in the output of --split-currencies has a row by currency
+        let output: &str = "client,available,held,total,locked,currency\n1,1.0,0.0,1.0,false,EUR\n2,1.0,0.0,1.0,false,EUR\n1,2.0,0.0,2.0,false,USD\n";
+        assert_eq!(
+            Client::clients_from_reader(output.as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "Line 4: client 1 is repeated"
+        );
+    }
+
+    #[test]
+    fn clients_json_test() {
+        let clients: Vec<Client> = vec![Client::new(1)];
+
+        assert_eq!(
+            Client::clients_to_json(clients.clone(), false).unwrap(),
+            "[{\"client\":1,\"available\":0.0000,\"held\":0.0000,\"total\":0.0000,\"locked\":false}]\n"
+        );
+
+        let pretty: String = String::from(
+            "[\n  {\n    \"client\": 1,\n    \"available\": 0.0000,\n    \"held\": 0.0000,\n    \"total\": 0.0000,\n    \"locked\": false\n  }\n]\n",
+        );
+        assert_eq!(
+            Client::clients_to_json(clients.clone(), true).unwrap(),
+            pretty
+        );
+
+        let config: Config = Config {
+            format: OutputFormat::JsonMap,
+            pretty: true,
+            ..Config::default()
+        };
+        let mut data: Vec<u8> = Vec::new();
+        Client::write_clients(clients, &mut data, &config).unwrap();
+        assert!(String::from_utf8(data)
+            .unwrap()
+            .starts_with("{\n  \"1\": {\n    \"client\": 1,"));
+    }
+
+    #[test]
+    fn locked_client_json_test() {
+        let client: Client = Client::new(1)
+            .new_transaction(TransactionType::Deposit, amount("2.5"))
+            .new_transaction(TransactionType::Dispute, amount("1.0"))
+            .new_transaction(TransactionType::Chargeback, amount("1.0"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&Client::clients_to_json(vec![client], false).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "client": 1,
+                "available": 1.5,
+                "held": 0.0,
+                "total": 1.5,
+                "locked": true
+            }])
+        );
+        //The amounts are numbers with the four decimal places of the text
+        assert!(json[0]["total"].is_number());
+        assert_eq!(
+            Client::clients_to_json(vec![client], false).unwrap(),
+            "[{\"client\":1,\"available\":1.5000,\"held\":0.0000,\"total\":1.5000,\"locked\":true}]\n"
+        );
+    }
+
+    #[test]
+    fn empty_output_test() {
+        let output = |format: OutputFormat, with_type_counts: bool| -> String {
+            let config: Config = Config {
+                format,
+                with_type_counts,
+                ..Config::default()
+            };
+            let transactions: Vec<Transaction> = Transaction::get_transactions_with(
+                "type, client, tx, amount\n".to_string(),
+                &config,
+            )
+            .unwrap();
+            let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+            for transaction in &transactions {
+                ledger.apply(transaction);
+            }
+
+            let mut data: Vec<u8> = Vec::new();
+            ledger.write_clients(&mut data, &config).unwrap();
+            String::from_utf8(data).unwrap()
+        };
+
+        assert_eq!(
+            output(OutputFormat::Csv, false),
+            "client,available,held,total,locked\n"
+        );
+        assert_eq!(
+            output(OutputFormat::Csv, true),
+            "client,available,held,total,locked,deposits,withdrawals,disputes,resolves,chargebacks\n"
+        );
+        assert_eq!(output(OutputFormat::Json, false), "[]\n");
+        assert_eq!(output(OutputFormat::JsonMap, false), "{}\n");
+        assert_eq!(
+            Client::clients_to_csv(Vec::new()).unwrap(),
+            "client,available,held,total,locked\n"
+        );
+    }
+
+    #[test]
+    fn failing_writer_test() {
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        //The error is returned to the caller instead of a panic
+        let clients: Vec<Client> = vec![Client::new(1)];
+        assert!(
+            Client::clients_to_writer(clients.clone(), FailingWriter, &Config::default()).is_err()
+        );
+        assert!(Client::write_clients(clients.clone(), FailingWriter, &Config::default()).is_err());
+
+        //The JSON is written the same way, its errors are returned by clients_to_json
+        let json: Config = Config {
+            format: OutputFormat::Json,
+            ..Config::default()
+        };
+        assert!(Client::write_clients(clients.clone(), FailingWriter, &json).is_err());
+        assert!(Client::clients_to_json(clients.clone(), false).is_ok());
+        assert!(Client::clients_to_csv(clients).is_ok());
+    }
+
+    #[test]
+    fn json_lines_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        for (tx_type, client, tx, value) in [
+            (TransactionType::Deposit, 1, 1, "1.5"),
+            (TransactionType::Deposit, 2, 2, "2.0"),
+            (TransactionType::Dispute, 2, 2, "0.0"),
+        ] {
+            ledger.apply(&Transaction::new(tx_type, client, tx, amount(value)));
+        }
+        let config: Config = Config {
+            format: OutputFormat::JsonLines,
+            with_totals: true,
+            ..Config::default()
+        };
+        let mut data: Vec<u8> = Vec::new();
+        ledger.write_clients(&mut data, &config).unwrap();
+        let output: String = String::from_utf8(data).unwrap();
+
+        //Every line parses on its own
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            serde_json::json!({"client": 1, "available": 1.5, "held": 0.0, "total": 1.5, "locked": false})
+        );
+        assert!(output.starts_with("{\"client\":1,\"available\":1.5000,"));
+        assert_eq!(lines[1]["client"], 2);
+        assert_eq!(lines[1]["held"], 2.0);
+        assert_eq!(lines[2]["totals"]["total"], 3.5);
+
+        //Without clients only the totals are written, and without them nothing, not even an empty line
+        let mut data: Vec<u8> = Vec::new();
+        Client::write_clients(Vec::new(), &mut data, &config).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "{\"totals\":{\"available\":0.0000,\"held\":0.0000,\"total\":0.0000,\"locked\":0}}\n"
+        );
+        let config: Config = Config {
+            format: OutputFormat::JsonLines,
+            ..Config::default()
+        };
+        let mut data: Vec<u8> = Vec::new();
+        Client::write_clients(Vec::new(), &mut data, &config).unwrap();
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn client_order_test() {
+        let output = |client_order: ClientOrder| -> Vec<u32> {
+            let mut ledger: ClientLedger = ClientLedger::new();
+            for (client, tx) in [(3, 1), (1, 2), (2, 3), (3, 4)] {
+                ledger.apply(&Transaction::new(
+                    TransactionType::Deposit,
+                    client,
+                    tx,
+                    amount("1.0"),
+                ));
+            }
+            let config: Config = Config {
+                client_order,
+                ..Config::default()
+            };
+            let mut data: Vec<u8> = Vec::new();
+            ledger.write_clients(&mut data, &config).unwrap();
+
+            String::from_utf8(data)
+                .unwrap()
+                .lines()
+                .skip(1)
+                .map(|row| row.split(',').next().unwrap().parse().unwrap())
+                .collect()
+        };
+
+        assert_eq!(output(ClientOrder::ClientId), [1, 2, 3]);
+        assert_eq!(output(ClientOrder::FirstSeen), [3, 1, 2]);
+    }
+
+    #[test]
+    fn totals_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        for (tx_type, client, tx, value) in [
+            (TransactionType::Deposit, 1, 1, "1.5"),
+            (TransactionType::Deposit, 2, 2, "2.25"),
+            (TransactionType::Deposit, 3, 3, "4.0"),
+            (TransactionType::Deposit, 3, 4, "3.0"),
+            (TransactionType::Dispute, 2, 2, "0.0"),
+            (TransactionType::Dispute, 3, 3, "0.0"),
+            (TransactionType::Chargeback, 3, 3, "0.0"),
+        ] {
+            ledger.apply(&Transaction::new(tx_type, client, tx, amount(value)));
+        }
+        let output = |format: OutputFormat| -> String {
+            let config: Config = Config {
+                format,
+                with_totals: true,
+                ..Config::default()
+            };
+            let mut data: Vec<u8> = Vec::new();
+            let clients: Vec<Client> = ledger.clients().copied().collect();
+            Client::write_clients(clients, &mut data, &config).unwrap();
+            String::from_utf8(data).unwrap()
+        };
+
+        let csv: String = output(OutputFormat::Csv);
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[4], "total,4.5000,2.2500,6.7500,1");
+
+        //The totals are the sums of the client rows
+        let sum = |column: usize| -> Amount {
+            rows[1..4]
+                .iter()
+                .map(|row| amount(row.split(',').nth(column).unwrap()))
+                .fold(Amount::ZERO, |sum, value| sum + value)
+        };
+        assert_eq!(
+            (sum(1), sum(2), sum(3)),
+            (amount("4.5"), amount("2.25"), amount("6.75"))
+        );
+
+        assert!(output(OutputFormat::Json).ends_with(
+            "],\"totals\":{\"available\":4.5000,\"held\":2.2500,\"total\":6.7500,\"locked\":1}}\n"
+        ));
+        assert!(output(OutputFormat::JsonMap).starts_with("{\"clients\":{\"1\":"));
+    }
+
+    #[test]
+    fn pad_client_id_test() {
+        let output = |format: OutputFormat| -> String {
+            let config: Config = Config {
+                format,
+                pad_client_id: Some(6),
+                ..Config::default()
+            };
+            let mut data: Vec<u8> = Vec::new();
+            Client::write_clients(
+                vec![Client::new(1), Client::new(1234567)],
+                &mut data,
+                &config,
+            )
+            .unwrap();
+            String::from_utf8(data).unwrap()
+        };
+
+        assert_eq!(
+            output(OutputFormat::Csv),
+            "client,available,held,total,locked\n000001,0.0000,0.0000,0.0000,false\n1234567,0.0000,0.0000,0.0000,false\n"
+        );
+        assert!(output(OutputFormat::Json).starts_with("[{\"client\":\"000001\","));
+        assert!(output(OutputFormat::JsonMap).starts_with("{\"000001\":{\"client\":\"000001\","));
+    }
+
+    #[test]
+    fn output_precision_test() {
+        let output = |format: OutputFormat| -> String {
+            let config: Config = Config {
+                format,
+                output_precision: HashMap::from([
+                    ("total".to_string(), 2),
+                    ("available".to_string(), 4),
+                ]),
+                ..Config::default()
+            };
+            let client: Client =
+                Client::new(1).new_transaction(TransactionType::Deposit, amount("1.2355"));
+            let mut data: Vec<u8> = Vec::new();
+            Client::write_clients(vec![client], &mut data, &config).unwrap();
+            String::from_utf8(data).unwrap()
+        };
+
+        //Held has no precision of its own and keeps four decimals
+        assert_eq!(
+            output(OutputFormat::Csv),
+            "client,available,held,total,locked\n1,1.2355,0.0000,1.24,false\n"
+        );
+        assert_eq!(
+            output(OutputFormat::Json),
+            "[{\"client\":1,\"available\":1.2355,\"held\":0.0000,\"total\":1.24,\"locked\":false}]\n"
+        );
+    }
+
+    #[test]
+    fn currency_precision_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        for (client, tx, value, currency) in [
+            (1, 1, "1500.0", Some("JPY")),
+            (2, 2, "10.255", Some("USD")),
+            (3, 3, "1.2345", Some("XYZ")),
+            (4, 4, "1.2345", None),
+        ] {
+            let mut deposit: Transaction =
+                Transaction::new(TransactionType::Deposit, client, tx, amount(value));
+            deposit.currency = currency.map(|c| c.to_string());
+            ledger.apply(&deposit);
+        }
+        assert_eq!(ledger.currency(1), Some("JPY"));
+
+        let mut data: Vec<u8> = Vec::new();
+        ledger.write_clients(&mut data, &Config::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "client,available,held,total,locked\n1,1500,0,1500,false\n2,10.26,0.00,10.26,false\n3,1.2345,0.0000,1.2345,false\n4,1.2345,0.0000,1.2345,false\n"
+        );
+    }
+
+    #[test]
+    fn accounting_negatives_test() {
+        let output = |accounting_negatives: bool| -> String {
+            let config: Config = Config {
+                accounting_negatives,
+                ..Config::default()
+            };
+            let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+            for (tx_type, tx, value) in [
+                ("deposit", 1, "100.0"),
+                ("withdrawal", 2, "50.0"),
+                ("dispute", 1, "0.0"),
+                ("chargeback", 1, "0.0"),
+            ] {
+                ledger.apply(&Transaction::new(
+                    TransactionType::parse(tx_type).unwrap(),
+                    1,
+                    tx,
+                    amount(value),
+                ));
+            }
+            let mut data: Vec<u8> = Vec::new();
+            ledger.write_clients(&mut data, &config).unwrap();
+            String::from_utf8(data).unwrap()
+        };
+
+        assert_eq!(
+            output(false),
+            "client,available,held,total,locked\n1,-50.0000,0.0000,-50.0000,true\n"
+        );
+        assert_eq!(
+            output(true),
+            "client,available,held,total,locked\n1,(50.0000),0.0000,(50.0000),true\n"
+        );
+    }
+
+    #[test]
+    fn changed_only_test() {
+        let config: Config = Config {
+            changed_only: true,
+            ..Config::default()
+        };
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        ledger.seed([
+            Client::new(1).new_transaction(TransactionType::Deposit, amount("5.0")),
+            Client::new(2).new_transaction(TransactionType::Deposit, amount("3.0")),
+        ]);
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            2,
+            1,
+            amount("1.0"),
+        ));
+        //An ignored withdrawal leaves client 1 as it was seeded
+        ledger.apply(&Transaction::new(
+            TransactionType::Withdrawal,
+            1,
+            2,
+            amount("9.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            3,
+            3,
+            amount("2.0"),
+        ));
+
+        let mut data: Vec<u8> = Vec::new();
+        ledger.write_clients(&mut data, &config).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "client,available,held,total,locked\n2,4.0000,0.0000,4.0000,false\n3,2.0000,0.0000,2.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn locked_reason_test() {
+        let config: Config = Config {
+            format: OutputFormat::Json,
+            with_locked_reason: true,
+            ..Config::default()
+        };
+        let mut ledger: ClientLedger = ClientLedger::with_config(&config);
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("3.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            2,
+            amount("2.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            2,
+            3,
+            amount("1.0"),
+        ));
+        //The second chargeback keeps the tx that locked the client
+        for (tx_type, tx) in [
+            ("dispute", 1),
+            ("chargeback", 1),
+            ("dispute", 2),
+            ("chargeback", 2),
+        ] {
+            ledger.apply(&Transaction::new(
+                TransactionType::parse(tx_type).unwrap(),
+                1,
+                tx,
+                amount("0.0"),
+            ));
+        }
+
+        let client: &Client = ledger.get(1).unwrap();
+        assert_eq!(client.locked_reason(), Some(LockReason::Chargeback));
+        assert_eq!(client.locked_at_tx(), Some(1));
+        assert_eq!(ledger.get(2).unwrap().locked_reason(), None);
+
+        let mut data: Vec<u8> = Vec::new();
+        ledger.write_clients(&mut data, &config).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "[{\"client\":1,\"available\":0.0000,\"held\":0.0000,\"total\":0.0000,\"locked\":true,\"locked_reason\":\"chargeback\",\"locked_at_tx\":1},\
+{\"client\":2,\"available\":1.0000,\"held\":0.0000,\"total\":1.0000,\"locked\":false,\"locked_reason\":null,\"locked_at_tx\":null}]\n"
+        );
+    }
+
+    #[test]
+    fn type_counts_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("10.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            2,
+            amount("5.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Withdrawal,
+            1,
+            3,
+            amount("2.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Resolve,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            2,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Chargeback,
+            1,
+            2,
+            amount("0.0"),
+        ));
+
+        let config: Config = Config {
+            with_type_counts: true,
+            ..Config::default()
+        };
+        let mut data: Vec<u8> = Vec::new();
+        Client::write_clients(ledger.into_clients(), &mut data, &config).unwrap();
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "client,available,held,total,locked,deposits,withdrawals,disputes,resolves,chargebacks\n\
+             1,8.0000,0.0000,8.0000,true,2,1,2,1,1\n"
+        );
+    }
+
+    #[test]
+    fn held_breakdown_test() {
+        let mut ledger: ClientLedger = ClientLedger::new();
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            amount("10.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            2,
+            amount("5.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            1,
+            3,
+            amount("2.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            1,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Dispute,
+            1,
+            2,
+            amount("0.0"),
+        ));
+        ledger.apply(&Transaction::new(
+            TransactionType::Deposit,
+            2,
+            4,
+            amount("1.0"),
+        ));
+
+        let config: Config = Config {
+            format: OutputFormat::Json,
+            held_breakdown: true,
+            ..Config::default()
+        };
+        let mut data: Vec<u8> = Vec::new();
+        ledger.write_clients(&mut data, &config).unwrap();
+
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "[{\"client\":1,\"available\":2.0000,\"held\":15.0000,\"total\":17.0000,\"locked\":false,\"held_by_dispute\":{\"1\":10.0000,\"2\":5.0000}},\
+             {\"client\":2,\"available\":1.0000,\"held\":0.0000,\"total\":1.0000,\"locked\":false,\"held_by_dispute\":{}}]\n"
+        );
+    }
+}
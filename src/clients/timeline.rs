@@ -0,0 +1,97 @@
+use super::super::money::Amount;
+use super::models::Client;
+use serde::Serialize;
+use std::io::Write;
+
+/// Balances of a client right after one of its transactions, a row of the `--timeline` output.
+/// # Examples
+/// ```
+/// # use accounts_cli::clients::models::Client;
+/// # use accounts_cli::clients::timeline::TimelineRow;
+/// # use accounts_cli::transactions::models::TransactionType;
+/// let client: Client = Client::new(1).new_transaction(TransactionType::Deposit, "2.0".parse().unwrap());
+/// let row: TimelineRow = TimelineRow::new(7, &client);
+/// assert_eq!((row.client, row.tx, row.total), (1, 7, "2.0".parse().unwrap()));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct TimelineRow {
+    pub client: u32,
+    pub tx: u32,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+}
+
+impl TimelineRow {
+    /// The balances of the client after the transaction with the tx ID.
+    pub fn new(tx: u32, client: &Client) -> Self {
+        Self {
+            client: client.client,
+            tx,
+            available: client.available,
+            held: client.held,
+            total: client.total,
+        }
+    }
+}
+
+/// Write the timeline as CSV, the rows of each client together and in the order they were applied.
+/// The clients are sorted by ID.
+///
+/// # Errors
+///
+/// Returns the [`csv::Error`] if a row can't be serialized or written.
+pub fn write_timeline<W: Write>(rows: &[TimelineRow], writer: W) -> Result<(), csv::Error> {
+    let mut sorted: Vec<&TimelineRow> = rows.iter().collect();
+    sorted.sort_by_key(|row| row.client);
+
+    let mut timeline_csv: csv::Writer<W> = csv::Writer::from_writer(writer);
+    for row in sorted {
+        timeline_csv.serialize(row)?;
+    }
+
+    //Without rows the header is still written
+    if rows.is_empty() {
+        timeline_csv.write_record(["client", "tx", "available", "held", "total"])?;
+    }
+
+    timeline_csv.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn write_timeline_test() {
+        let row = |client: u32, tx: u32, total: &str| -> TimelineRow {
+            TimelineRow {
+                client,
+                tx,
+                available: amount(total),
+                held: Amount::ZERO,
+                total: amount(total),
+            }
+        };
+        let rows: Vec<TimelineRow> = vec![row(2, 1, "1.0"), row(1, 2, "3.0"), row(2, 3, "0.5")];
+
+        let mut data: Vec<u8> = Vec::new();
+        write_timeline(&rows, &mut data).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "client,tx,available,held,total\n1,2,3.0000,0.0000,3.0000\n2,1,1.0000,0.0000,1.0000\n2,3,0.5000,0.0000,0.5000\n"
+        );
+
+        let mut data: Vec<u8> = Vec::new();
+        write_timeline(&[], &mut data).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "client,tx,available,held,total\n"
+        );
+    }
+}
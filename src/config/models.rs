@@ -0,0 +1,828 @@
+use super::super::money::Amount;
+use super::super::transactions::fixed_width::FixedWidthSpec;
+use super::super::transactions::models::TRANSACTION_TYPES;
+use encoding_rs::Encoding;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Format of the clients in the output.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// One row per client, with a header.
+    #[default]
+    Csv,
+    /// An array of client objects.
+    Json,
+    /// An object with the clients by ID.
+    JsonMap,
+    /// A client object per line, each line is a JSON document.
+    JsonLines,
+}
+
+/// What a dispute uses to find the transaction it references.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum DisputeKey {
+    /// The tx ID of the dispute is the one of the original transaction.
+    #[default]
+    Tx,
+    /// The `ref` column of the dispute is the one of the original transaction.
+    Ref,
+}
+
+/// Order of the transactions before they are applied.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ProcessOrder {
+    /// The order of the input.
+    File,
+    /// By tx ID.
+    #[default]
+    Tx,
+    /// By the `timestamp` column.
+    Timestamp,
+}
+
+/// Order of the clients in the output.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ClientOrder {
+    /// By client ID.
+    #[default]
+    ClientId,
+    /// The order the clients were first seen in the input.
+    FirstSeen,
+}
+
+impl ClientOrder {
+    /// Parse the value of `--order`: `client-id` or `first-seen`.
+    pub fn parse(order: &str) -> Result<ClientOrder, String> {
+        match order {
+            "client-id" => Ok(ClientOrder::ClientId),
+            "first-seen" => Ok(ClientOrder::FirstSeen),
+            other => Err(format!("Unknown client order {}", other)),
+        }
+    }
+}
+
+/// Order of the transactions with the same timestamp, with `--process-order timestamp`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum TimestampTiebreak {
+    /// The order of the input, the files in the order they were given.
+    #[default]
+    File,
+    /// By tx ID.
+    Tx,
+}
+
+impl TimestampTiebreak {
+    /// Parse the value of `--timestamp-tiebreak`: `file` or `tx`.
+    pub fn parse(tiebreak: &str) -> Result<TimestampTiebreak, String> {
+        match tiebreak {
+            "file" => Ok(TimestampTiebreak::File),
+            "tx" => Ok(TimestampTiebreak::Tx),
+            other => Err(format!("Unknown timestamp tiebreak {}", other)),
+        }
+    }
+}
+
+impl ProcessOrder {
+    /// Parse the value of `--process-order`: `file`, `tx` or `timestamp`.
+    pub fn parse(order: &str) -> Result<ProcessOrder, String> {
+        match order {
+            "file" => Ok(ProcessOrder::File),
+            "tx" => Ok(ProcessOrder::Tx),
+            "timestamp" => Ok(ProcessOrder::Timestamp),
+            other => Err(format!("Unknown process order {}", other)),
+        }
+    }
+}
+
+impl DisputeKey {
+    /// Parse the value of `--dispute-key`: `tx` or `ref`.
+    pub fn parse(key: &str) -> Result<DisputeKey, String> {
+        match key {
+            "tx" => Ok(DisputeKey::Tx),
+            "ref" => Ok(DisputeKey::Ref),
+            other => Err(format!("Unknown dispute key {}", other)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Parse the value of `--format`: `csv`, `json`, `json-map` or `jsonl`.
+    pub fn parse(format: &str) -> Result<OutputFormat, String> {
+        match format {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "json-map" => Ok(OutputFormat::JsonMap),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            other => Err(format!("Unknown output format {}", other)),
+        }
+    }
+}
+
+/// Options for a run of the CLI, parsed from the command line arguments.
+/// # Examples
+/// ```
+/// # use accounts_cli::config::models::Config;
+/// # use std::env;
+/// let args: Vec<String> = env::args().collect();
+/// let config: Config = Config::from_args(&args[1..]).unwrap_or_default();
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    /// Paths of the input files, applied together as a single input.
+    pub paths: Vec<String>,
+    /// Read the input as fixed-width records instead of CSV.
+    pub fixed_width: Option<FixedWidthSpec>,
+    /// Reject input amounts with more decimal places than this.
+    pub max_input_decimals: Option<usize>,
+    /// Process the input in a single pass with [`run_streaming`](crate::run_streaming).
+    pub stream: bool,
+    /// Format of the output.
+    pub format: OutputFormat,
+    /// Indent the JSON output.
+    pub pretty: bool,
+    /// Add a column with the number of transactions of each type.
+    pub with_type_counts: bool,
+    /// Add the sums of the balances of all the clients, and the number of locked ones, after the clients.
+    pub with_totals: bool,
+    /// Report and skip the records that can't be read instead of stopping.
+    pub skip_bad_rows: bool,
+    /// Decompress the input with gzip, also done for paths ending in `.gz`.
+    pub gzip: bool,
+    /// What disputes use to find the original transaction.
+    pub dispute_key: DisputeKey,
+    /// Add the held amount of each open dispute to the JSON output.
+    pub held_breakdown: bool,
+    /// Read transactions and commands from stdin with [`repl::run`](crate::repl::run) instead of a file.
+    pub repl: bool,
+    /// Stop if the header doesn't have the expected columns, instead of a warning.
+    pub strict_header: bool,
+    /// Stop at a repeated tx ID, instead of keeping the first row with it.
+    pub strict_tx_ids: bool,
+    /// Order of the transactions before they are applied, `--stream` always uses the file order.
+    pub process_order: ProcessOrder,
+    /// Order of the clients in the output.
+    pub client_order: ClientOrder,
+    /// Floor the total of the clients at zero and add a column with the clamped amount.
+    pub clamp_negative_total: bool,
+    /// Encoding of the input, transcoded to UTF-8 before it's read. [`None`] is UTF-8.
+    pub input_encoding: Option<&'static Encoding>,
+    /// Report the duration and throughput of the run in stderr.
+    pub time: bool,
+    /// Log in stderr why each transaction was skipped or rejected and the summary of the run.
+    pub verbose: bool,
+    /// Ignore the disputes with an amount higher than the disputed deposit times this ratio.
+    pub max_dispute_ratio: Option<Amount>,
+    /// Reject the rows with an amount larger than this, positive or negative.
+    pub max_amount: Option<Amount>,
+    /// Write the client IDs zero-padded to this number of digits.
+    pub pad_client_id: Option<usize>,
+    /// Reject the rows with a client or counterparty ID above this, like `65535` for 16-bit IDs.
+    /// [`None`] allows any `u32`.
+    pub max_client_id: Option<u32>,
+    /// Synonyms of the transaction types, replaced by the type they map to when the records are read.
+    pub type_aliases: HashMap<String, String>,
+    /// Path of the JSON manifest of the run, see [`Manifest`](crate::manifest::Manifest).
+    pub manifest: Option<String>,
+    /// Path of the file the clients are written to, instead of stdout.
+    pub output: Option<String>,
+    /// Read the amounts with a comma as the decimal separator, like `1,5`.
+    pub decimal_comma: bool,
+    /// Delimiter of the fields of the CSV input, a comma if [`None`].
+    pub delimiter: Option<u8>,
+    /// Write only the clients whose balances changed from the ones they were seeded with.
+    pub changed_only: bool,
+    /// Multiply every amount read by this factor, see [`Amount::checked_mul`].
+    pub scale: Option<Amount>,
+    /// Warn in stderr if there are more clients than this ratio of the transactions.
+    pub client_ratio_warning: Option<Amount>,
+    /// Stop the run after the first chargeback and write the clients as they are then.
+    pub stop_on_chargeback: bool,
+    /// Add the reason and the tx ID of the lock of the clients to the output.
+    pub with_locked_reason: bool,
+    /// Report in stderr the disputes, resolves and chargebacks whose deposit was not found.
+    pub report_unmatched: bool,
+    /// Decimal places written for each amount column, the others are written with four.
+    pub output_precision: HashMap<String, usize>,
+    /// Stop applying transactions once the run takes longer than this, and write what was applied.
+    pub timeout: Option<Duration>,
+    /// Path of the CSV with the balances of each client after each of its transactions.
+    pub timeline: Option<String>,
+    /// Path of a client CSV of an earlier run, the opening balances of the clients.
+    pub opening: Option<String>,
+    /// Order of the transactions with the same timestamp.
+    pub timestamp_tiebreak: TimestampTiebreak,
+    /// Merge the consecutive deposits of a client that no dispute references before applying them.
+    pub coalesce_deposits: bool,
+    /// Write the negative amounts in parentheses, like `(50.0000)`, instead of with a minus.
+    pub accounting_negatives: bool,
+    /// Only check the rows with [`check_all`](crate::check_all) and report the errors, without the clients.
+    pub check: bool,
+    /// Keep the balances of each client in each currency apart, and write a row for each of them.
+    pub split_currencies: bool,
+    /// Apply the transactions of the clients in shards on several threads, see [`process_all`](crate::process_all).
+    pub parallel: bool,
+}
+
+impl Config {
+    /// Parse the arguments, without the program name.
+    /// The arguments that are not options are taken as the input paths.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if an option is unknown or its value is missing or invalid,
+    /// if `--held-breakdown` is used with the CSV format, or if `--stream` is used with an order other than `file`.
+    pub fn from_args(args: &[String]) -> Result<Config, String> {
+        let mut config: Config = Config::default();
+        let mut args = args.iter();
+        let mut process_order: Option<ProcessOrder> = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--fixed-width" => {
+                    let spec: &String = args
+                        .next()
+                        .ok_or("Option --fixed-width needs a column spec")?;
+                    config.fixed_width = Some(FixedWidthSpec::parse(spec)?);
+                }
+                "--max-input-decimals" => {
+                    let max: &String = args
+                        .next()
+                        .ok_or("Option --max-input-decimals needs a number")?;
+                    config.max_input_decimals = Some(
+                        max.parse()
+                            .map_err(|_| format!("Invalid --max-input-decimals {}", max))?,
+                    );
+                }
+                "--stream" => config.stream = true,
+                "--format" => {
+                    let format: &String = args.next().ok_or("Option --format needs a format")?;
+                    config.format = OutputFormat::parse(format)?;
+                }
+                "--pretty" => config.pretty = true,
+                "--with-type-counts" => config.with_type_counts = true,
+                "--with-totals" => config.with_totals = true,
+                "--skip-bad-rows" => config.skip_bad_rows = true,
+                "--gzip" => config.gzip = true,
+                "--dispute-key" => {
+                    let key: &String = args.next().ok_or("Option --dispute-key needs tx or ref")?;
+                    config.dispute_key = DisputeKey::parse(key)?;
+                }
+                "--held-breakdown" => config.held_breakdown = true,
+                "--repl" => config.repl = true,
+                "--strict-header" => config.strict_header = true,
+                "--strict-tx-ids" => config.strict_tx_ids = true,
+                "--check" => config.check = true,
+                "--split-currencies" => config.split_currencies = true,
+                "--parallel" => config.parallel = true,
+                "--clamp-negative-total" => config.clamp_negative_total = true,
+                "--time" => config.time = true,
+                "-v" | "--verbose" => config.verbose = true,
+                "--decimal-comma" => config.decimal_comma = true,
+                "--output" => {
+                    let path: &String = args.next().ok_or("Option --output needs a path")?;
+                    config.output = Some(path.to_string());
+                }
+                "--delimiter" => {
+                    let delimiter: &String = args
+                        .next()
+                        .ok_or("Option --delimiter needs a character or tab")?;
+                    config.delimiter = Some(Config::parse_delimiter(delimiter)?);
+                }
+                "--changed-only" => config.changed_only = true,
+                "--stop-on-chargeback" => config.stop_on_chargeback = true,
+                "--with-locked-reason" => config.with_locked_reason = true,
+                "--report-unmatched" => config.report_unmatched = true,
+                "--coalesce-deposits" => config.coalesce_deposits = true,
+                "--accounting-negatives" => config.accounting_negatives = true,
+                "--input-encoding" => {
+                    let label: &String = args
+                        .next()
+                        .ok_or("Option --input-encoding needs an encoding")?;
+                    config.input_encoding = Some(
+                        Encoding::for_label(label.as_bytes())
+                            .ok_or(format!("Unknown encoding {}", label))?,
+                    );
+                }
+                "--max-dispute-ratio" => {
+                    let ratio: &String = args
+                        .next()
+                        .ok_or("Option --max-dispute-ratio needs a ratio")?;
+                    config.max_dispute_ratio = match ratio.parse::<Amount>() {
+                        Ok(x) if !x.is_negative() => Some(x),
+                        _ => return Err(format!("Invalid --max-dispute-ratio {}", ratio)),
+                    };
+                }
+                "--max-amount" => {
+                    let max: &String = args.next().ok_or("Option --max-amount needs an amount")?;
+                    config.max_amount = match max.parse::<Amount>() {
+                        Ok(x) if !x.is_negative() => Some(x),
+                        _ => return Err(format!("Invalid --max-amount {}", max)),
+                    };
+                }
+                "--max-client-id" => {
+                    let max: &String = args
+                        .next()
+                        .ok_or("Option --max-client-id needs a client ID")?;
+                    config.max_client_id = Some(
+                        max.parse()
+                            .map_err(|_| format!("Invalid --max-client-id {}", max))?,
+                    );
+                }
+                "--pad-client-id" => {
+                    let width: &String = args
+                        .next()
+                        .ok_or("Option --pad-client-id needs a number of digits")?;
+                    config.pad_client_id = Some(
+                        width
+                            .parse()
+                            .map_err(|_| format!("Invalid --pad-client-id {}", width))?,
+                    );
+                }
+                "--type-alias" => {
+                    let aliases: &String = args
+                        .next()
+                        .ok_or("Option --type-alias needs a list of alias=type")?;
+                    config
+                        .type_aliases
+                        .extend(Config::parse_type_aliases(aliases)?);
+                }
+                "--output-precision-per-field" => {
+                    let fields: &String = args.next().ok_or(
+                        "Option --output-precision-per-field needs a list of field=decimals",
+                    )?;
+                    config
+                        .output_precision
+                        .extend(Config::parse_output_precision(fields)?);
+                }
+                "--timeline" => {
+                    let path: &String = args.next().ok_or("Option --timeline needs a path")?;
+                    config.timeline = Some(path.to_string());
+                }
+                "--opening" => {
+                    let path: &String = args.next().ok_or("Option --opening needs a path")?;
+                    config.opening = Some(path.to_string());
+                }
+                "--manifest" => {
+                    let path: &String = args.next().ok_or("Option --manifest needs a path")?;
+                    config.manifest = Some(path.to_string());
+                }
+                "--scale" => {
+                    let factor: &String = args.next().ok_or("Option --scale needs a factor")?;
+                    config.scale = Some(
+                        factor
+                            .parse()
+                            .map_err(|_| format!("Invalid --scale {}", factor))?,
+                    );
+                }
+                "--client-ratio-warning" => {
+                    let ratio: &String = args
+                        .next()
+                        .ok_or("Option --client-ratio-warning needs a ratio")?;
+                    config.client_ratio_warning = match ratio.parse::<Amount>() {
+                        Ok(x) if !x.is_negative() => Some(x),
+                        _ => return Err(format!("Invalid --client-ratio-warning {}", ratio)),
+                    };
+                }
+                "--timeout" => {
+                    let seconds: &String = args
+                        .next()
+                        .ok_or("Option --timeout needs a number of seconds")?;
+                    config.timeout = match seconds.parse::<f64>().map(Duration::try_from_secs_f64) {
+                        Ok(Ok(x)) if !x.is_zero() => Some(x),
+                        _ => return Err(format!("Invalid --timeout {}", seconds)),
+                    };
+                }
+                "--timestamp-tiebreak" => {
+                    let tiebreak: &String = args
+                        .next()
+                        .ok_or("Option --timestamp-tiebreak needs file or tx")?;
+                    config.timestamp_tiebreak = TimestampTiebreak::parse(tiebreak)?;
+                }
+                "--process-order" => {
+                    let order: &String = args
+                        .next()
+                        .ok_or("Option --process-order needs file, tx or timestamp")?;
+                    process_order = Some(ProcessOrder::parse(order)?);
+                }
+                "--order" => {
+                    let order: &String = args
+                        .next()
+                        .ok_or("Option --order needs client-id or first-seen")?;
+                    config.client_order = ClientOrder::parse(order)?;
+                }
+                option if option.starts_with("--") => {
+                    return Err(format!("Unknown option {}", option));
+                }
+                path => config.paths.push(path.to_string()),
+            }
+        }
+
+        match process_order {
+            Some(order) if config.stream && order != ProcessOrder::File => {
+                return Err(
+                    "Option --stream applies the transactions in file order, use --process-order file"
+                        .to_string(),
+                );
+            }
+            Some(order) => config.process_order = order,
+            None if config.stream => config.process_order = ProcessOrder::File,
+            None => {}
+        }
+
+        if config.coalesce_deposits && (config.with_type_counts || config.timeline.is_some()) {
+            return Err(
+                "Option --coalesce-deposits merges deposits, it can't be used with --with-type-counts or --timeline"
+                    .to_string(),
+            );
+        }
+
+        if config.coalesce_deposits && config.process_order == ProcessOrder::File {
+            return Err(
+                "Option --coalesce-deposits needs the transactions read in full, use --process-order tx or timestamp"
+                    .to_string(),
+            );
+        }
+
+        if config.split_currencies
+            && (config.format == OutputFormat::JsonMap
+                || config.timeline.is_some()
+                || config.report_unmatched
+                || config.opening.is_some())
+        {
+            return Err(
+                "Option --split-currencies writes a client once by currency, it can't be used with --format json-map, --timeline, --report-unmatched or --opening"
+                    .to_string(),
+            );
+        }
+
+        if config.parallel
+            && (config.process_order == ProcessOrder::File
+                || config.stop_on_chargeback
+                || config.timeout.is_some()
+                || config.timeline.is_some()
+                || config.split_currencies
+                || config.report_unmatched)
+        {
+            return Err(
+                "Option --parallel applies the clients apart, it can't be used with --stream, --process-order file, --stop-on-chargeback, --timeout, --timeline, --split-currencies or --report-unmatched"
+                    .to_string(),
+            );
+        }
+
+        if config.held_breakdown && config.format == OutputFormat::Csv {
+            return Err("Option --held-breakdown needs a JSON --format".to_string());
+        }
+
+        if config.pretty && config.format == OutputFormat::JsonLines {
+            return Err(
+                "Option --pretty writes a client over several lines, it can't be used with --format jsonl"
+                    .to_string(),
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Parse the value of `--delimiter`, a single ASCII character like `;` or `tab` for a tab.
+    /// Letters, digits, quotes and new lines can't be delimiters.
+    fn parse_delimiter(delimiter: &str) -> Result<u8, String> {
+        match delimiter.as_bytes() {
+            b"tab" | b"\\t" => Ok(b'\t'),
+            &[byte] if (byte.is_ascii_punctuation() || byte == b'\t') && byte != b'"' => Ok(byte),
+            _ => Err(format!("Invalid --delimiter {}", delimiter)),
+        }
+    }
+
+    /// Returns a builder of the CSV readers of the input, with the `delimiter` and every field trimmed,
+    /// so a header like `type, client, tx, amount` is read the same as without the spaces.
+    pub fn csv_reader(&self) -> csv::ReaderBuilder {
+        let mut builder: csv::ReaderBuilder = csv::ReaderBuilder::new();
+        builder
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .delimiter(self.delimiter.unwrap_or(b','));
+        builder
+    }
+
+    /// Parse the value of `--type-alias`, a list like `credit=deposit,debit=withdrawal`.
+    /// Every alias must map to one of the [`TRANSACTION_TYPES`].
+    fn parse_type_aliases(aliases: &str) -> Result<HashMap<String, String>, String> {
+        let mut map: HashMap<String, String> = HashMap::new();
+
+        for alias in aliases.split(',') {
+            let (name, tx_type) = alias
+                .split_once('=')
+                .ok_or(format!("Invalid type alias {}", alias))?;
+            let (name, tx_type): (&str, &str) = (name.trim(), tx_type.trim());
+
+            if name.is_empty() || !TRANSACTION_TYPES.contains(&tx_type) {
+                return Err(format!("Invalid type alias {}", alias));
+            }
+            map.insert(name.to_string(), tx_type.to_string());
+        }
+
+        Ok(map)
+    }
+
+    /// Parse the value of `--output-precision-per-field`, a list like `total=2,available=4`.
+    /// Every field must be an amount column and the decimals can't be more than [`Amount::DECIMALS`].
+    fn parse_output_precision(fields: &str) -> Result<HashMap<String, usize>, String> {
+        let mut map: HashMap<String, usize> = HashMap::new();
+
+        for field in fields.split(',') {
+            let (name, decimals) = field
+                .split_once('=')
+                .ok_or(format!("Invalid output precision {}", field))?;
+            let name: &str = name.trim();
+
+            match decimals.trim().parse::<usize>() {
+                Ok(decimals) if AMOUNT_FIELDS.contains(&name) && decimals <= Amount::DECIMALS => {
+                    map.insert(name.to_string(), decimals);
+                }
+                _ => return Err(format!("Invalid output precision {}", field)),
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Output columns with an amount, the ones `--output-precision-per-field` can change.
+const AMOUNT_FIELDS: [&str; 4] = ["available", "held", "total", "clamped"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn from_args_test() {
+        let config: Config = Config::from_args(&args(&[
+            "--fixed-width",
+            "type=0-10,client=10-15,tx=15-20",
+            "tx.txt",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.paths, ["tx.txt"]);
+        assert!(config.fixed_width.is_some());
+
+        let config: Config =
+            Config::from_args(&args(&["tx.csv", "--max-input-decimals", "4"])).unwrap();
+        assert_eq!(config.max_input_decimals, Some(4));
+        assert!(Config::from_args(&args(&["--max-input-decimals", "four"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--stream", "tx.csv"]))
+                .unwrap()
+                .stream
+        );
+
+        let config: Config =
+            Config::from_args(&args(&["--format", "json-map", "--pretty", "tx.csv"])).unwrap();
+        assert_eq!(config.format, OutputFormat::JsonMap);
+        assert_eq!(
+            Config::from_args(&args(&["--format", "jsonl", "tx.csv"]))
+                .unwrap()
+                .format,
+            OutputFormat::JsonLines
+        );
+        assert!(Config::from_args(&args(&["--format", "jsonl", "--pretty", "tx.csv"])).is_err());
+        assert!(config.pretty);
+        assert_eq!(
+            Config::from_args(&args(&["--order", "first-seen", "tx.csv"]))
+                .unwrap()
+                .client_order,
+            ClientOrder::FirstSeen
+        );
+        assert!(Config::from_args(&args(&["--order", "name", "tx.csv"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--with-totals", "tx.csv"]))
+                .unwrap()
+                .with_totals
+        );
+        assert!(Config::from_args(&args(&["--format", "xml"])).is_err());
+
+        let config: Config = Config::from_args(&args(&["--dispute-key", "ref"])).unwrap();
+        assert_eq!(config.dispute_key, DisputeKey::Ref);
+        assert!(Config::from_args(&args(&["--dispute-key", "id"])).is_err());
+
+        assert!(
+            Config::from_args(&args(&["--held-breakdown", "--format", "json"]))
+                .unwrap()
+                .held_breakdown
+        );
+        assert!(Config::from_args(&args(&["--held-breakdown", "tx.csv"])).is_err());
+        assert!(Config::from_args(&args(&["--repl"])).unwrap().repl);
+
+        let config: Config =
+            Config::from_args(&args(&["--process-order", "timestamp", "tx.csv"])).unwrap();
+        assert_eq!(config.process_order, ProcessOrder::Timestamp);
+        assert_eq!(
+            Config::from_args(&args(&["--stream"]))
+                .unwrap()
+                .process_order,
+            ProcessOrder::File
+        );
+        assert!(Config::from_args(&args(&["--stream", "--process-order", "tx"])).is_err());
+        assert!(Config::from_args(&args(&["--process-order", "date"])).is_err());
+        assert_eq!(
+            Config::from_args(&args(&["--timestamp-tiebreak", "tx", "tx.csv"]))
+                .unwrap()
+                .timestamp_tiebreak,
+            TimestampTiebreak::Tx
+        );
+        assert!(Config::from_args(&args(&["--timestamp-tiebreak", "client"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--coalesce-deposits", "tx.csv"]))
+                .unwrap()
+                .coalesce_deposits
+        );
+        assert!(Config::from_args(&args(&["--coalesce-deposits", "--stream"])).is_err());
+        assert!(Config::from_args(&args(&["--coalesce-deposits", "--with-type-counts"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--accounting-negatives", "tx.csv"]))
+                .unwrap()
+                .accounting_negatives
+        );
+        assert!(
+            Config::from_args(&args(&["--clamp-negative-total"]))
+                .unwrap()
+                .clamp_negative_total
+        );
+
+        let config: Config =
+            Config::from_args(&args(&["--input-encoding", "latin1", "tx.csv"])).unwrap();
+        assert_eq!(config.input_encoding, Some(encoding_rs::WINDOWS_1252));
+        assert!(Config::from_args(&args(&["--input-encoding", "klingon"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--time", "tx.csv"]))
+                .unwrap()
+                .time
+        );
+        assert!(Config::from_args(&args(&["-v", "tx.csv"])).unwrap().verbose);
+        assert!(
+            Config::from_args(&args(&["--verbose", "tx.csv"]))
+                .unwrap()
+                .verbose
+        );
+        assert!(
+            Config::from_args(&args(&["--strict-header", "tx.csv"]))
+                .unwrap()
+                .strict_header
+        );
+        let delimiter =
+            |value: &str| Config::from_args(&args(&["--delimiter", value])).map(|c| c.delimiter);
+        assert_eq!(delimiter(";"), Ok(Some(b';')));
+        assert_eq!(delimiter("tab"), Ok(Some(b'\t')));
+        assert_eq!(delimiter("\t"), Ok(Some(b'\t')));
+        assert!(delimiter("ab").is_err());
+        assert!(delimiter("x").is_err());
+        assert!(delimiter("\"").is_err());
+        assert_eq!(Config::default().delimiter, None);
+        let config: Config =
+            Config::from_args(&args(&["--output", "accounts.csv", "tx.csv"])).unwrap();
+        assert_eq!(config.output.as_deref(), Some("accounts.csv"));
+        assert_eq!(config.paths, ["tx.csv"]);
+        assert!(Config::from_args(&args(&["tx.csv", "--output"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--check", "tx.csv"]))
+                .unwrap()
+                .check
+        );
+        assert!(
+            Config::from_args(&args(&["--split-currencies", "tx.csv"]))
+                .unwrap()
+                .split_currencies
+        );
+        assert!(Config::from_args(&args(&["--split-currencies", "--format", "json-map"])).is_err());
+        assert!(Config::from_args(&args(&["--split-currencies", "--report-unmatched"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--split-currencies", "--opening", "yesterday.csv"]))
+                .is_err()
+        );
+        assert!(
+            Config::from_args(&args(&["--parallel", "tx.csv"]))
+                .unwrap()
+                .parallel
+        );
+        assert!(Config::from_args(&args(&["--parallel", "--stream"])).is_err());
+        assert!(Config::from_args(&args(&["--parallel", "--stop-on-chargeback"])).is_err());
+        assert!(
+            Config::from_args(&args(&["--strict-tx-ids", "tx.csv"]))
+                .unwrap()
+                .strict_tx_ids
+        );
+
+        let config: Config =
+            Config::from_args(&args(&["--max-dispute-ratio", "1.1", "tx.csv"])).unwrap();
+        assert_eq!(config.max_dispute_ratio, Some("1.1".parse().unwrap()));
+        assert!(Config::from_args(&args(&["--max-dispute-ratio", "-1"])).is_err());
+        assert!(Config::from_args(&args(&["--max-dispute-ratio", "half"])).is_err());
+        let config: Config =
+            Config::from_args(&args(&["--max-amount", "1000000", "tx.csv"])).unwrap();
+        assert_eq!(config.max_amount, Some("1000000".parse().unwrap()));
+        assert!(Config::from_args(&args(&["--max-amount", "-1"])).is_err());
+        assert!(Config::from_args(&args(&["--max-amount", "inf"])).is_err());
+
+        let config: Config =
+            Config::from_args(&args(&["--opening", "yesterday.csv", "tx.csv"])).unwrap();
+        assert_eq!(config.opening, Some("yesterday.csv".to_string()));
+        assert_eq!(config.paths, ["tx.csv"]);
+        assert!(Config::from_args(&args(&["--opening"])).is_err());
+
+        let config: Config =
+            Config::from_args(&args(&["--max-client-id", "65535", "tx.csv"])).unwrap();
+        assert_eq!(config.max_client_id, Some(u16::MAX as u32));
+        assert!(Config::from_args(&args(&["--max-client-id", "-1"])).is_err());
+
+        let config: Config = Config::from_args(&args(&["--pad-client-id", "6", "tx.csv"])).unwrap();
+        assert_eq!(config.pad_client_id, Some(6));
+        assert!(Config::from_args(&args(&["--pad-client-id", "-6"])).is_err());
+
+        let config: Config =
+            Config::from_args(&args(&["--type-alias", "credit=deposit, debit=withdrawal"]))
+                .unwrap();
+        assert_eq!(config.type_aliases["credit"], "deposit");
+        assert_eq!(config.type_aliases["debit"], "withdrawal");
+        assert!(Config::from_args(&args(&["--type-alias", "credit=refund"])).is_err());
+        assert!(Config::from_args(&args(&["--type-alias", "credit"])).is_err());
+
+        let config: Config = Config::from_args(&args(&[
+            "--output-precision-per-field",
+            "total=2, available=4",
+            "tx.csv",
+        ]))
+        .unwrap();
+        assert_eq!(config.output_precision["total"], 2);
+        assert_eq!(config.output_precision["available"], 4);
+        assert!(!config.output_precision.contains_key("held"));
+        assert!(Config::from_args(&args(&["--output-precision-per-field", "total=5"])).is_err());
+        assert!(Config::from_args(&args(&["--output-precision-per-field", "locked=2"])).is_err());
+
+        let config: Config = Config::from_args(&args(&["--timeout", "1.5", "tx.csv"])).unwrap();
+        assert_eq!(config.timeout, Some(Duration::from_millis(1500)));
+        assert!(Config::from_args(&args(&["--timeout", "0"])).is_err());
+        assert!(Config::from_args(&args(&["--timeout", "-1"])).is_err());
+        assert!(Config::from_args(&args(&["--timeout", "soon"])).is_err());
+
+        let config: Config =
+            Config::from_args(&args(&["--timeline", "timeline.csv", "tx.csv"])).unwrap();
+        assert_eq!(config.timeline, Some("timeline.csv".to_string()));
+        assert!(Config::from_args(&args(&["--timeline"])).is_err());
+
+        let config: Config =
+            Config::from_args(&args(&["--manifest", "run.json", "tx.csv"])).unwrap();
+        assert_eq!(config.manifest, Some("run.json".to_string()));
+        assert_eq!(config.paths, ["tx.csv"]);
+        assert!(Config::from_args(&args(&["--manifest"])).is_err());
+
+        assert!(
+            Config::from_args(&args(&["--decimal-comma", "tx.csv"]))
+                .unwrap()
+                .decimal_comma
+        );
+
+        let config: Config = Config::from_args(&args(&["a.csv", "--stream", "b.csv"])).unwrap();
+        assert_eq!(config.paths, ["a.csv", "b.csv"]);
+
+        assert!(
+            Config::from_args(&args(&["--changed-only", "tx.csv"]))
+                .unwrap()
+                .changed_only
+        );
+
+        let config: Config = Config::from_args(&args(&["--scale", "0.001", "tx.csv"])).unwrap();
+        assert_eq!(config.scale, Some("0.001".parse().unwrap()));
+        assert!(Config::from_args(&args(&["--scale", "1e3"])).is_err());
+
+        let config: Config =
+            Config::from_args(&args(&["--client-ratio-warning", "0.9", "tx.csv"])).unwrap();
+        assert_eq!(config.client_ratio_warning, Some("0.9".parse().unwrap()));
+        assert!(Config::from_args(&args(&["--client-ratio-warning", "-1"])).is_err());
+
+        assert!(
+            Config::from_args(&args(&["--stop-on-chargeback", "tx.csv"]))
+                .unwrap()
+                .stop_on_chargeback
+        );
+
+        assert!(
+            Config::from_args(&args(&["--with-locked-reason", "tx.csv"]))
+                .unwrap()
+                .with_locked_reason
+        );
+
+        assert!(
+            Config::from_args(&args(&["--report-unmatched", "tx.csv"]))
+                .unwrap()
+                .report_unmatched
+        );
+
+        assert!(Config::from_args(&args(&["--fixed-width"])).is_err());
+        assert!(Config::from_args(&args(&["--unknown", "tx.csv"])).is_err());
+    }
+}
@@ -0,0 +1,1499 @@
+pub mod clients;
+pub mod config;
+pub mod manifest;
+pub mod money;
+pub mod repl;
+pub mod transactions;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+
+use clients::ledger::{ClientLedger, Outcome, ProcessReport, Unmatched};
+use clients::models::Client;
+use clients::timeline::TimelineRow;
+use config::models::{Config, ProcessOrder};
+use transactions::models::{DepositRefs, Transaction, TransactionError, TransactionType};
+use transactions::records::TransactionRecords;
+
+/// Error of a run, from reading the input to writing the output.
+#[derive(Debug)]
+pub enum ProcessError {
+    /// A record doesn't pass the checks of the [`Config`].
+    Transaction(TransactionError),
+    /// The CSV can't be read or written.
+    Csv(csv::Error),
+    /// The input can't be read.
+    Io(io::Error),
+    /// The output can't be written.
+    Output(io::Error),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::Transaction(e) => write!(f, "{}", e),
+            ProcessError::Csv(e) => write!(f, "Something went wrong with the CSV {}", e),
+            ProcessError::Io(e) => write!(f, "Something went wrong reading {}", e),
+            ProcessError::Output(e) => write!(f, "Something went wrong writing the output {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+impl From<TransactionError> for ProcessError {
+    fn from(e: TransactionError) -> Self {
+        ProcessError::Transaction(e)
+    }
+}
+
+impl From<csv::Error> for ProcessError {
+    fn from(e: csv::Error) -> Self {
+        ProcessError::Csv(e)
+    }
+}
+
+impl From<io::Error> for ProcessError {
+    fn from(e: io::Error) -> Self {
+        ProcessError::Io(e)
+    }
+}
+
+/// Rows applied in a run and the time it took, reported in stderr with `--time`.
+/// # Examples
+/// ```
+/// # use accounts_cli::RunTime;
+/// # use std::time::Duration;
+/// let time: RunTime = RunTime {
+///     rows: 1000,
+///     elapsed: Duration::from_millis(500),
+/// };
+/// assert_eq!(time.to_string(), "Processed 1000 rows in 0.500s\nThroughput 2000 rows/s");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RunTime {
+    pub rows: u64,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for RunTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds: f64 = self.elapsed.as_secs_f64();
+        writeln!(f, "Processed {} rows in {:.3}s", self.rows, seconds)?;
+
+        //A run too short to be measured has no throughput
+        if seconds > 0.0 {
+            write!(f, "Throughput {:.0} rows/s", self.rows as f64 / seconds)
+        } else {
+            write!(f, "Throughput unknown rows/s")
+        }
+    }
+}
+
+/// Everything a run gives back, for embedding the engine in a service with [`process`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LedgerResult {
+    /// The clients in the order they were first seen.
+    pub clients: Vec<Client>,
+    /// The clients serialized in the `format` of the [`Config`], the same as the CLI writes.
+    pub report: String,
+    /// Tx ID of the last transaction applied, [`None`] if there were none.
+    pub last_tx_id: Option<u32>,
+    /// Rows applied and the time it took.
+    pub stats: RunTime,
+    /// The chargeback the run stopped at with `stop_on_chargeback`.
+    pub stopped_at: Option<Transaction>,
+    /// Disputes, resolves and chargebacks whose deposit was not found, with `report_unmatched`.
+    pub unmatched: Vec<Unmatched>,
+    /// The run took longer than the `timeout` and stopped, the clients are the ones applied until then.
+    pub timed_out: bool,
+    /// Balances of the clients after each transaction applied, with `timeline`.
+    pub timeline: Vec<TimelineRow>,
+    /// Transactions skipped for locked clients and withdrawals rejected for missing funds, see [`ClientLedger::report`].
+    pub process_report: ProcessReport,
+}
+
+/// Path that reads the input from stdin, also used when no path is given.
+pub const STDIN_PATH: &str = "-";
+
+/// Open the input file, decompressing it if `gzip` is set or the path ends in `.gz`.
+/// With an `input_encoding` the content is transcoded to UTF-8 as it's read.
+/// The path `-` reads the input from stdin instead of a file.
+///
+/// # Errors
+///
+/// Returns the [`io::Error`] if the file can't be opened.
+/// # Examples
+/// ```no_run
+/// # use accounts_cli::config::models::Config;
+/// # use accounts_cli::transactions::models::Transaction;
+/// # use std::io::Read;
+/// let config: Config = Config::default();
+/// let mut tx: String = String::new();
+/// accounts_cli::open_input("transactions.csv.gz", &config)?.read_to_string(&mut tx)?;
+/// let transactions: Vec<Transaction> = Transaction::get_transactions(tx)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn open_input(path: &str, config: &Config) -> io::Result<Box<dyn Read>> {
+    let file: Box<dyn Read> = match path {
+        STDIN_PATH => Box::new(io::stdin()),
+        path => Box::new(File::open(path)?),
+    };
+
+    let input: Box<dyn Read> = if config.gzip || path.ends_with(".gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        file
+    };
+
+    match config.input_encoding {
+        Some(encoding) => Ok(Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(Some(encoding))
+                .build(input),
+        )),
+        None => Ok(input),
+    }
+}
+
+/// Read the transactions, apply them and write the clients in a single pass.
+/// Each record is applied to a [`ClientLedger`] as soon as it's read and the clients are serialized
+/// straight into the writer, so neither the transactions nor the output are ever held in full.
+///
+/// Unlike [`Transaction::get_transactions`] the transactions are not sorted by the ID,
+/// they are applied in the order of the input.
+///
+/// # Errors
+///
+/// Returns a [`ProcessError`] if a record can't be read or doesn't pass the checks,
+/// or if the output can't be written. Returns the number of rows applied otherwise.
+/// # Examples
+/// ```
+/// use accounts_cli::config::models::Config;
+///
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+/// let mut output: Vec<u8> = Vec::new();
+///
+/// accounts_cli::run_streaming(tx.as_bytes(), &mut output, &Config::default()).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n"
+/// );
+/// ```
+pub fn run_streaming<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    config: &Config,
+) -> Result<u64, ProcessError> {
+    let mut ledger: ClientLedger = ClientLedger::with_config(config);
+    ledger.seed(opening_clients(config)?);
+    let mut applied: Applied = Applied::new(config);
+    apply_records(
+        reader,
+        &mut ledger,
+        &mut DepositRefs::default(),
+        &mut applied,
+        config,
+        &mut |_, _, _| {},
+    )?;
+    applied.report(&ledger);
+
+    ledger
+        .write_clients(writer, config)
+        .map_err(ProcessError::Output)?;
+    Ok(applied.rows)
+}
+
+/// Read the transactions, apply them in the `process_order` of the [`Config`] and return the
+/// clients together with the output, the last tx ID and the stats of the run.
+/// With the `file` order the records are applied as they are read, like [`run_streaming`],
+/// otherwise the transactions are read in full and sorted first.
+///
+/// # Errors
+///
+/// Returns a [`ProcessError`] if the input can't be read, or if a record can't be read or doesn't pass the checks.
+/// # Examples
+/// ```
+/// use accounts_cli::config::models::Config;
+/// use accounts_cli::LedgerResult;
+///
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 2, 1.0\ndeposit, 1, 1, 2.0\n";
+/// let result: LedgerResult = accounts_cli::process(&Config::default(), tx.as_bytes()).unwrap();
+///
+/// assert_eq!(result.clients.len(), 1);
+/// assert_eq!(result.last_tx_id, Some(2));
+/// assert_eq!(result.stats.rows, 2);
+/// assert_eq!(
+///     result.report,
+///     "client,available,held,total,locked\n1,3.0000,0.0000,3.0000,false\n"
+/// );
+/// ```
+pub fn process<R: Read>(config: &Config, reader: R) -> Result<LedgerResult, ProcessError> {
+    process_all(config, [reader])
+}
+
+/// Apply a CSV of transactions with the default options and return the clients as CSV, in one call.
+/// It's [`process`] for the common case, use it with a [`Config`] for the other options.
+///
+/// # Errors
+///
+/// Returns a [`ProcessError`] if a record can't be read or doesn't pass the checks.
+/// # Examples
+/// ```
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 2.0\nwithdrawal, 1, 2, 0.5\n";
+///
+/// assert_eq!(
+///     accounts_cli::process_csv(tx).unwrap(),
+///     "client,available,held,total,locked\n1,1.5000,0.0000,1.5000,false\n"
+/// );
+/// assert!(accounts_cli::process_csv("type, client, tx, amount\ndeposit, one, 1, 2.0\n").is_err());
+/// ```
+pub fn process_csv(input: &str) -> Result<String, ProcessError> {
+    process(&Config::default(), input.as_bytes()).map(|result| result.report)
+}
+
+/// Same as [`process`], for async services: the input is read without blocking the runtime, then
+/// the transactions are applied like [`process`] does. Only with the `async` feature.
+///
+/// # Errors
+///
+/// Returns a [`ProcessError`] if the input can't be read, or if a record can't be read or doesn't pass the checks.
+/// # Examples
+/// ```
+/// # use accounts_cli::config::models::Config;
+/// # use accounts_cli::LedgerResult;
+/// # #[cfg(feature = "async")]
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let tx: &[u8] = b"type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+/// let result: LedgerResult = accounts_cli::process_async(&Config::default(), tx).await.unwrap();
+/// assert_eq!(result.clients.len(), 1);
+/// # });
+/// ```
+#[cfg(feature = "async")]
+pub async fn process_async<R: tokio::io::AsyncRead + Unpin>(
+    config: &Config,
+    mut reader: R,
+) -> Result<LedgerResult, ProcessError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut input: Vec<u8> = Vec::new();
+    reader.read_to_end(&mut input).await?;
+    process(config, input.as_slice())
+}
+
+/// Same as [`process`], but for several inputs applied together to the same ledger, each with its own header.
+/// Clients accumulate across the inputs and a dispute can reference a deposit of an earlier input.
+/// With an order other than `file` the transactions of all the inputs are sorted together.
+/// With `parallel` the transactions of the clients are then applied in shards on several threads, unless
+/// there are transfers or disputes of another client, which are always applied one after the other.
+///
+/// # Errors
+///
+/// Returns a [`ProcessError`] if an input can't be read, or if a record can't be read or doesn't pass the checks.
+/// # Examples
+/// ```
+/// use accounts_cli::config::models::Config;
+/// use accounts_cli::LedgerResult;
+///
+/// let a: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+/// let b: &str = "type, client, tx, amount\ndeposit, 1, 2, 2.0\n";
+/// let result: LedgerResult =
+///     accounts_cli::process_all(&Config::default(), [a.as_bytes(), b.as_bytes()]).unwrap();
+/// assert_eq!(result.clients.len(), 1);
+/// ```
+pub fn process_all<R: Read>(
+    config: &Config,
+    readers: impl IntoIterator<Item = R>,
+) -> Result<LedgerResult, ProcessError> {
+    process_all_with(config, readers, |_, _, _| {})
+}
+
+/// Same as [`process_all`], calling `on_transaction` after each transaction is applied or rejected,
+/// with its client as it is then and the [`Outcome`], for embedders that emit metrics or traces.
+///
+/// # Errors
+///
+/// Returns a [`ProcessError`] if an input can't be read, or if a record can't be read or doesn't pass the checks.
+/// # Examples
+/// ```
+/// use accounts_cli::clients::ledger::Outcome;
+/// use accounts_cli::config::models::Config;
+///
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\nwithdrawal, 1, 2, 5.0\n";
+/// let mut rejected: u32 = 0;
+/// accounts_cli::process_all_with(&Config::default(), [tx.as_bytes()], |_, _, outcome| {
+///     if outcome == Outcome::Rejected {
+///         rejected += 1;
+///     }
+/// })
+/// .unwrap();
+/// assert_eq!(rejected, 1);
+/// ```
+pub fn process_all_with<R: Read, F: FnMut(&Transaction, &Client, Outcome)>(
+    config: &Config,
+    readers: impl IntoIterator<Item = R>,
+    mut on_transaction: F,
+) -> Result<LedgerResult, ProcessError> {
+    let start: Instant = Instant::now();
+    let mut ledger: ClientLedger = ClientLedger::with_config(config);
+    let opening: Vec<Client> = opening_clients(config)?;
+    ledger.seed(opening.iter().copied());
+    let mut refs: DepositRefs = DepositRefs::default();
+    let mut applied: Applied = Applied::new(config);
+
+    match config.process_order {
+        ProcessOrder::File => {
+            for reader in readers {
+                if !apply_records(
+                    reader,
+                    &mut ledger,
+                    &mut refs,
+                    &mut applied,
+                    config,
+                    &mut on_transaction,
+                )? {
+                    break;
+                }
+            }
+        }
+        _ => {
+            let mut transactions: Vec<Transaction> = Vec::new();
+            //The tx IDs are checked across the inputs, like they are in file order
+            //The deadline is checked while the inputs are read and before they're sorted
+            for reader in readers {
+                transactions.extend(Transaction::read_transactions(
+                    reader,
+                    &mut refs,
+                    config,
+                    applied.deadline,
+                )?);
+                if applied.expired() {
+                    break;
+                }
+            }
+            if applied.expired() {
+                transactions.clear();
+            }
+            Transaction::sort_transactions(&mut transactions, config);
+            if config.coalesce_deposits {
+                transactions = Transaction::coalesce_deposits(transactions, config);
+            }
+
+            if config.parallel && independent(&transactions, config) {
+                ledger = apply_parallel(
+                    transactions,
+                    &opening,
+                    config,
+                    &mut applied,
+                    &mut on_transaction,
+                );
+            } else {
+                for transaction in transactions {
+                    if !applied.apply(&mut ledger, transaction, config, &mut on_transaction) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    applied.report(&ledger);
+
+    let clients: Vec<Client> = ledger.clients().copied().collect();
+    let unmatched: Vec<Unmatched> = ledger.unmatched().to_vec();
+    let process_report: ProcessReport = ledger.report().clone();
+    let mut report: Vec<u8> = Vec::new();
+    ledger
+        .write_clients(&mut report, config)
+        .map_err(ProcessError::Output)?;
+
+    Ok(LedgerResult {
+        clients,
+        report: String::from_utf8(report).expect("the output is always UTF-8"),
+        last_tx_id: applied.last_tx_id,
+        stats: RunTime {
+            rows: applied.rows,
+            elapsed: start.elapsed(),
+        },
+        stopped_at: applied.stopped_at,
+        unmatched,
+        timed_out: applied.timed_out,
+        timeline: applied.timeline,
+        process_report,
+    })
+}
+
+/// Returns the clients of the `opening` CSV of the [`Config`], none without it.
+fn opening_clients(config: &Config) -> Result<Vec<Client>, ProcessError> {
+    match &config.opening {
+        Some(path) => Ok(Client::clients_from_reader(File::open(path)?)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Rows that pass the checks and the errors of the rows that don't, found by [`check_all`].
+#[derive(Debug, Default, PartialEq)]
+pub struct CheckReport {
+    /// Rows that pass every check.
+    pub valid: u64,
+    /// Error of each row that doesn't pass, or of a header that can't be read.
+    pub errors: Vec<TransactionError>,
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Valid rows {}\nErrors {}", self.valid, self.errors.len())?;
+        for e in &self.errors {
+            write!(f, "\n{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read every row of the inputs with the checks of [`process_all`], numbers, types, negative amounts
+/// and repeated tx IDs, without applying them. Unlike a run, every error is collected instead of
+/// stopping at the first one, and a repeated tx ID is an error even without `strict_tx_ids`.
+/// # Examples
+/// ```
+/// use accounts_cli::config::models::Config;
+/// use accounts_cli::CheckReport;
+///
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, one, 2, 1.0\ndeposit, 1, 1, 2.0\n";
+/// let report: CheckReport = accounts_cli::check_all(&Config::default(), [tx.as_bytes()]);
+/// assert_eq!(report.valid, 1);
+/// assert_eq!(
+///     report.to_string(),
+///     "Valid rows 1\nErrors 2\nLine 3: invalid client ID \"one\"\nLine 4: tx ID 1 is used by another transaction"
+/// );
+/// ```
+pub fn check_all<R: Read>(config: &Config, readers: impl IntoIterator<Item = R>) -> CheckReport {
+    let mut refs: DepositRefs = DepositRefs::default();
+    let mut report: CheckReport = CheckReport::default();
+
+    for reader in readers {
+        match TransactionRecords::new(reader, &mut refs, config) {
+            Ok(records) => {
+                for result in records {
+                    match result {
+                        Ok(_) => report.valid += 1,
+                        Err(e) => report.errors.push(e),
+                    }
+                }
+            }
+            Err(e) => report.errors.push(e),
+        }
+    }
+
+    report
+}
+
+/// Apply the records to the ledger as they are read, in file order.
+/// Returns `false` if the run stopped at a chargeback or timed out.
+fn apply_records<R: Read>(
+    reader: R,
+    ledger: &mut ClientLedger,
+    refs: &mut DepositRefs,
+    applied: &mut Applied,
+    config: &Config,
+    on_transaction: &mut OnTransaction,
+) -> Result<bool, ProcessError> {
+    for result in TransactionRecords::new(reader, refs, config)?.skip_bad_rows() {
+        if !applied.apply(ledger, result?, config, on_transaction) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Hook called with each transaction, see [`process_all_with`].
+type OnTransaction<'a> = dyn FnMut(&Transaction, &Client, Outcome) + 'a;
+
+/// A transaction applied by a shard of [`apply_parallel`], with its index in the run, the [`Outcome`]
+/// and its client as it was then.
+type Step = (usize, Transaction, Outcome, Client);
+
+/// Returns if the transactions of each client can be applied apart from the ones of the others, for `parallel`:
+/// there are no transfers and every dispute, resolve and chargeback references a transaction of its own client.
+fn independent(transactions: &[Transaction], config: &Config) -> bool {
+    let mut owners: HashMap<String, u32> = HashMap::new();
+    for transaction in transactions {
+        if transaction.tx_type == TransactionType::Transfer {
+            return false;
+        }
+        if !transaction.is_dispute() {
+            if let Some(key) = transaction.dispute_key(config.dispute_key) {
+                owners.insert(key, transaction.client);
+            }
+        }
+    }
+
+    transactions
+        .iter()
+        .filter(|transaction| transaction.is_dispute())
+        .all(|transaction| {
+            transaction
+                .dispute_key(config.dispute_key)
+                .and_then(|key| owners.get(&key))
+                .is_none_or(|client| *client == transaction.client)
+        })
+}
+
+/// Apply the transactions on the rayon threads with `parallel`, each thread with the clients of a shard
+/// of the IDs, then merge the ledgers of the shards. The `opening` clients are seeded in their shard and
+/// come first in the order. `on_transaction` is called afterwards, in the order
+/// of the transactions, with each client as it was after its transaction.
+fn apply_parallel(
+    transactions: Vec<Transaction>,
+    opening: &[Client],
+    config: &Config,
+    applied: &mut Applied,
+    on_transaction: &mut OnTransaction,
+) -> ClientLedger {
+    let shards: usize = rayon::current_num_threads();
+    let mut first_seen: HashMap<u32, usize> = HashMap::new();
+    for (index, client) in opening.iter().enumerate() {
+        first_seen.entry(client.id()).or_insert(index);
+    }
+    let mut applied_at: HashMap<u32, usize> = HashMap::new();
+    let mut partitions: Vec<Vec<(usize, Transaction)>> = (0..shards).map(|_| Vec::new()).collect();
+    for (index, transaction) in transactions.into_iter().enumerate() {
+        first_seen
+            .entry(transaction.client)
+            .or_insert(opening.len() + index);
+        applied_at.insert(transaction.tx, index);
+        partitions[transaction.client as usize % shards].push((index, transaction));
+    }
+
+    let results: Vec<(ClientLedger, Vec<Step>)> = partitions
+        .into_par_iter()
+        .enumerate()
+        .map(|(shard, partition)| {
+            let mut ledger: ClientLedger = ClientLedger::with_config(config);
+            ledger.seed(
+                opening
+                    .iter()
+                    .copied()
+                    .filter(|client| client.id() as usize % shards == shard),
+            );
+            let steps: Vec<Step> = partition
+                .into_iter()
+                .map(|(index, transaction)| {
+                    let outcome: Outcome = ledger.apply(&transaction);
+                    let client: Client = ledger
+                        .get(transaction.client)
+                        .copied()
+                        .unwrap_or(Client::new(transaction.client));
+                    (index, transaction, outcome, client)
+                })
+                .collect();
+            (ledger, steps)
+        })
+        .collect();
+
+    let mut ledger: ClientLedger = ClientLedger::with_config(config);
+    let mut steps: Vec<Step> = Vec::new();
+    for (shard, shard_steps) in results {
+        ledger.merge(shard, &first_seen, &applied_at);
+        steps.extend(shard_steps);
+    }
+
+    steps.sort_unstable_by_key(|step| step.0);
+    for (_, transaction, outcome, client) in steps {
+        on_transaction(&transaction, &client, outcome);
+        applied.rows += 1;
+        applied.last_tx_id = Some(transaction.tx);
+    }
+    ledger
+}
+
+/// What was applied to the ledger so far in a run.
+#[derive(Debug, Default)]
+struct Applied {
+    rows: u64,
+    last_tx_id: Option<u32>,
+    /// The chargeback the run stopped at with `stop_on_chargeback`.
+    stopped_at: Option<Transaction>,
+    /// When the run stops with `timeout`.
+    deadline: Option<Instant>,
+    timed_out: bool,
+    /// Balances after each transaction with `timeline`.
+    timeline: Vec<TimelineRow>,
+}
+
+impl Applied {
+    /// Start a run now, with the deadline of the `timeout` of the [`Config`].
+    fn new(config: &Config) -> Self {
+        Self {
+            deadline: config.timeout.map(|timeout| Instant::now() + timeout),
+            ..Self::default()
+        }
+    }
+
+    /// Returns if the deadline passed, the run is timed out then.
+    fn expired(&mut self) -> bool {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.timed_out = true;
+        }
+        self.timed_out
+    }
+
+    /// Apply the transaction to the ledger.
+    /// Returns `false` if it's a chargeback and the run stops with `stop_on_chargeback`,
+    /// or if the deadline passed, the transaction is not applied then.
+    fn apply(
+        &mut self,
+        ledger: &mut ClientLedger,
+        transaction: Transaction,
+        config: &Config,
+        on_transaction: &mut OnTransaction,
+    ) -> bool {
+        if self.expired() {
+            return false;
+        }
+
+        let outcome: Outcome = ledger.apply(&transaction);
+        self.rows += 1;
+
+        //With split_currencies the client is the one of the currency of the transaction
+        let ledger: &ClientLedger = ledger.ledger_for(&transaction);
+        if let Some(client) = ledger.get(transaction.client) {
+            on_transaction(&transaction, client, outcome);
+        }
+
+        //A transfer changes the counterparty too
+        if config.timeline.is_some() {
+            let counterparty: Option<u32> = transaction
+                .counterparty
+                .filter(|_| transaction.tx_type == TransactionType::Transfer);
+            for id in [Some(transaction.client), counterparty]
+                .into_iter()
+                .flatten()
+            {
+                if let Some(client) = ledger.get(id) {
+                    self.timeline.push(TimelineRow::new(transaction.tx, client));
+                }
+            }
+        }
+        self.last_tx_id = Some(transaction.tx);
+
+        if config.stop_on_chargeback && transaction.tx_type == TransactionType::Chargeback {
+            self.stopped_at = Some(transaction);
+            return false;
+        }
+        true
+    }
+
+    /// Log the warnings of the run, the unmatched disputes and the chargeback it stopped at,
+    /// and the summary of the run.
+    fn report(&self, ledger: &ClientLedger) {
+        log::info!(
+            "Processed {} rows of {} clients",
+            self.rows,
+            ledger.clients().count()
+        );
+        if let Some(warning) = ledger.client_ratio_warning(self.rows) {
+            log::warn!("{}", warning);
+        }
+        if !ledger.unmatched().is_empty() {
+            log::warn!("Unmatched references: {}", ledger.unmatched().len());
+            for unmatched in ledger.unmatched() {
+                log::warn!("{}", unmatched);
+            }
+        }
+        if self.timed_out {
+            log::warn!(
+                "Timed out after {} rows, the output has the rows applied until then",
+                self.rows
+            );
+        }
+        if let Some(transaction) = &self.stopped_at {
+            log::warn!(
+                "Stopped at the chargeback of tx {} for client {}, after {} rows",
+                transaction.tx,
+                transaction.client,
+                self.rows
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clients::ledger::RejectedWithdrawal;
+    use config::models::{ClientOrder, OutputFormat};
+    use money::Amount;
+
+    #[test]
+    fn run_streaming_test() {
+        let mut tx: String = String::from("type, client, tx, amount\n");
+        for id in 0..100_000 {
+            tx.push_str(&format!("deposit, {}, {}, 1.0\n", id % 100, id));
+        }
+
+        let mut output: Vec<u8> = Vec::new();
+        let rows: u64 = run_streaming(tx.as_bytes(), &mut output, &Config::default()).unwrap();
+        assert_eq!(rows, 100_000);
+
+        let output: String = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 101);
+        assert_eq!(lines[0], "client,available,held,total,locked");
+        assert_eq!(lines[1], "0,1000.0000,0.0000,1000.0000,false");
+        assert_eq!(lines[100], "99,1000.0000,0.0000,1000.0000,false");
+    }
+
+    #[test]
+    fn run_streaming_error_test() {
+        let config: Config = Config {
+            max_input_decimals: Some(2),
+            ..Config::default()
+        };
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.001\n";
+
+        let mut output: Vec<u8> = Vec::new();
+        let result = run_streaming(tx.as_bytes(), &mut output, &config);
+
+        assert!(matches!(result, Err(ProcessError::Transaction(_))));
+    }
+
+    #[test]
+    fn gzip_skip_bad_rows_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, 1, 2, not a number\ndeposit, 1, 3, 2.0\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(tx.as_bytes()).unwrap();
+
+        let path = std::env::temp_dir().join("accounts-cli-gzip-skip-bad-rows.csv.gz");
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        let path: &str = path.to_str().unwrap();
+
+        let config: Config = Config {
+            skip_bad_rows: true,
+            ..Config::default()
+        };
+
+        let mut data: String = String::new();
+        open_input(path, &config)
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        let transactions: Vec<Transaction> =
+            Transaction::get_transactions_with(data, &config).unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        let mut output: Vec<u8> = Vec::new();
+        run_streaming(open_input(path, &config).unwrap(), &mut output, &config).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n1,3.0000,0.0000,3.0000,false\n"
+        );
+
+        //Without the option the bad row stops the run
+        let mut output: Vec<u8> = Vec::new();
+        let result = run_streaming(
+            open_input(path, &Config::default()).unwrap(),
+            &mut output,
+            &Config::default(),
+        );
+        assert!(matches!(result, Err(ProcessError::Transaction(_))));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn gzip_corrupt_skip_bad_rows_test() {
+        let mut tx: String = String::from("type, client, tx, amount\n");
+        for id in 1..=20_000 {
+            tx.push_str(&format!("deposit, 1, {}, 1.0\n", id));
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(tx.as_bytes()).unwrap();
+        let mut compressed: Vec<u8> = encoder.finish().unwrap();
+        //The checksum of the data is the first field of the 8 bytes at the end
+        let checksum: usize = compressed.len() - 8;
+        compressed[checksum] ^= 0xff;
+
+        let path = std::env::temp_dir().join("accounts-cli-gzip-corrupt.csv.gz");
+        std::fs::write(&path, compressed).unwrap();
+        let path: &str = path.to_str().unwrap();
+        let config: Config = Config {
+            skip_bad_rows: true,
+            ..Config::default()
+        };
+
+        //An input that can't be read is never skipped like a bad row
+        let result = process(&config, open_input(path, &config).unwrap());
+        assert!(matches!(
+            result,
+            Err(ProcessError::Transaction(TransactionError::Read { .. }))
+        ));
+        let result = run_streaming(open_input(path, &config).unwrap(), Vec::new(), &config);
+        assert!(matches!(
+            result,
+            Err(ProcessError::Transaction(TransactionError::Read { .. }))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn gzip_matches_plain_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 5.0\ndeposit, 2, 2, 1.0\nwithdrawal, 1, 3, 1.5\ndispute, 2, 2\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(tx.as_bytes()).unwrap();
+        let compressed: Vec<u8> = encoder.finish().unwrap();
+
+        let expected: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+
+        //Found by the extension, or by the option for a path without it
+        let gz = std::env::temp_dir().join("accounts-cli-gzip-plain.csv.gz");
+        let flagged = std::env::temp_dir().join("accounts-cli-gzip-plain.bin");
+        std::fs::write(&gz, &compressed).unwrap();
+        std::fs::write(&flagged, &compressed).unwrap();
+        let gzip: Config = Config {
+            gzip: true,
+            ..Config::default()
+        };
+
+        for (path, config) in [(&gz, Config::default()), (&flagged, gzip)] {
+            let input: Box<dyn Read> = open_input(path.to_str().unwrap(), &config).unwrap();
+            let result: LedgerResult = process(&config, input).unwrap();
+            assert_eq!(result.clients, expected.clients);
+            assert_eq!(result.report, expected.report);
+        }
+
+        std::fs::remove_file(gz).unwrap();
+        std::fs::remove_file(flagged).unwrap();
+    }
+
+    #[test]
+    fn input_encoding_test() {
+        //"café" in Windows-1252, not valid UTF-8
+        let mut tx: Vec<u8> = b"type, client, tx, amount, memo\ndeposit, 1, 1, 2.5, caf".to_vec();
+        tx.push(0xE9);
+        tx.push(b'\n');
+
+        let path = std::env::temp_dir().join("accounts-cli-input-encoding.csv");
+        std::fs::write(&path, tx).unwrap();
+        let path: &str = path.to_str().unwrap();
+
+        let mut data: String = String::new();
+        assert!(open_input(path, &Config::default())
+            .unwrap()
+            .read_to_string(&mut data)
+            .is_err());
+
+        let config: Config = Config {
+            input_encoding: Some(encoding_rs::WINDOWS_1252),
+            ..Config::default()
+        };
+        let mut data: String = String::new();
+        open_input(path, &config)
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert!(data.ends_with("café\n"));
+
+        let mut output: Vec<u8> = Vec::new();
+        run_streaming(open_input(path, &config).unwrap(), &mut output, &config).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n1,2.5000,0.0000,2.5000,false\n"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn process_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 2, 3, 5.0\ndeposit, 1, 1, 1.0\nwithdrawal, 2, 2, 2.0\n";
+
+        //By tx ID the withdrawal comes before the deposit and is ignored
+        let result: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+        assert_eq!(result.clients.len(), 2);
+        assert_eq!(result.clients[0].id(), 1);
+        assert_eq!(result.clients[1].total(), "5.0".parse().unwrap());
+        assert_eq!(result.last_tx_id, Some(3));
+        assert_eq!(result.stats.rows, 3);
+        assert_eq!(
+            result.report,
+            "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n2,5.0000,0.0000,5.0000,false\n"
+        );
+        assert_eq!(
+            result.process_report.rejected_withdrawals,
+            [RejectedWithdrawal {
+                client: 2,
+                tx: 2,
+                requested: "2.0".parse().unwrap(),
+                available: Amount::ZERO,
+            }]
+        );
+
+        //The transactions of a locked client are skipped and counted
+        let locked: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndispute, 1, 1\nchargeback, 1, 1\ndeposit, 1, 2, 1.0\n";
+        let result: LedgerResult = process(&Config::default(), locked.as_bytes()).unwrap();
+        assert_eq!(result.process_report.skipped(), 1);
+
+        //In file order it's the same as run_streaming
+        let config: Config = Config {
+            process_order: ProcessOrder::File,
+            ..Config::default()
+        };
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        run_streaming(tx.as_bytes(), &mut output, &config).unwrap();
+        assert_eq!(result.report, String::from_utf8(output).unwrap());
+        assert_eq!(result.clients[0].total(), "3.0".parse().unwrap());
+        assert_eq!(result.last_tx_id, Some(2));
+
+        let config: Config = Config {
+            max_input_decimals: Some(2),
+            ..Config::default()
+        };
+        assert!(matches!(
+            process(
+                &config,
+                "type, client, tx, amount\ndeposit, 1, 1, 1.001\n".as_bytes()
+            ),
+            Err(ProcessError::Transaction(_))
+        ));
+    }
+
+    #[test]
+    fn process_all_test() {
+        let a: &str = "type, client, tx, amount\ndeposit, 1, 1, 10.0\ndeposit, 2, 2, 4.0\n";
+        let b: &str = "type, client, tx, amount\ndispute, 1, 1\ndeposit, 2, 3, 1.0\n";
+
+        for process_order in [ProcessOrder::File, ProcessOrder::Tx] {
+            let config: Config = Config {
+                process_order,
+                ..Config::default()
+            };
+            let result: LedgerResult = process_all(&config, [a.as_bytes(), b.as_bytes()]).unwrap();
+
+            //The dispute in b holds the deposit of a
+            assert_eq!(result.clients.len(), 2);
+            assert_eq!(result.clients[0].available(), "0.0".parse().unwrap());
+            assert_eq!(result.clients[0].held(), "10.0".parse().unwrap());
+            assert_eq!(result.clients[1].total(), "5.0".parse().unwrap());
+            assert_eq!(result.stats.rows, 4);
+        }
+
+        let config: Config = Config {
+            process_order: ProcessOrder::File,
+            ..Config::default()
+        };
+        let result: LedgerResult = process_all(&config, [a.as_bytes(), b.as_bytes()]).unwrap();
+        assert_eq!(result.last_tx_id, Some(3));
+    }
+
+    #[test]
+    fn stop_on_chargeback_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 10.0\ndeposit, 2, 2, 5.0\ndispute, 1, 1\nchargeback, 1, 1\ndeposit, 2, 3, 1.0\ndeposit, 3, 4, 1.0\n";
+
+        let config: Config = Config {
+            stop_on_chargeback: true,
+            process_order: ProcessOrder::File,
+            ..Config::default()
+        };
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+
+        //The chargeback is applied, nothing after it
+        assert_eq!(result.stats.rows, 4);
+        assert_eq!(result.last_tx_id, Some(1));
+        assert_eq!(
+            result.stopped_at.as_ref().unwrap().tx_type,
+            TransactionType::Chargeback
+        );
+        assert_eq!(
+            result.report,
+            "client,available,held,total,locked\n1,0.0000,0.0000,0.0000,true\n2,5.0000,0.0000,5.0000,false\n"
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(
+            run_streaming(tx.as_bytes(), &mut output, &config).unwrap(),
+            4
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), result.report);
+
+        //By tx ID the chargeback of tx 1 comes third
+        let config: Config = Config {
+            stop_on_chargeback: true,
+            ..Config::default()
+        };
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        assert_eq!(result.stats.rows, 3);
+        assert_eq!(result.clients.len(), 1);
+        let result: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+        assert_eq!(result.stats.rows, 6);
+        assert_eq!(result.stopped_at, None);
+    }
+
+    #[test]
+    fn empty_input_test() {
+        //A zero-byte file has no header either, even a strict one is not an error
+        for process_order in [ProcessOrder::File, ProcessOrder::Tx] {
+            let config: Config = Config {
+                strict_header: true,
+                process_order,
+                ..Config::default()
+            };
+            let result: LedgerResult = process(&config, "".as_bytes()).unwrap();
+            assert!(result.clients.is_empty());
+            assert_eq!(result.stats.rows, 0);
+            assert_eq!(result.last_tx_id, None);
+            assert_eq!(result.report, "client,available,held,total,locked\n");
+        }
+
+        let config: Config = Config {
+            strict_header: true,
+            format: OutputFormat::Json,
+            ..Config::default()
+        };
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(
+            run_streaming("".as_bytes(), &mut output, &config).unwrap(),
+            0
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "[]\n");
+
+        //A header with a blank name is still checked
+        assert!(process(&config, "\"\"\n".as_bytes()).is_err());
+    }
+
+    /// A reader that waits before giving each line, like a slow network input.
+    struct Throttled<'a> {
+        lines: std::str::SplitInclusive<'a, char>,
+        delay: Duration,
+    }
+
+    impl Read for Throttled<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            std::thread::sleep(self.delay);
+            match self.lines.next() {
+                Some(line) => {
+                    buf[..line.len()].copy_from_slice(line.as_bytes());
+                    Ok(line.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn timeout_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, 1, 2, 1.0\ndeposit, 1, 3, 1.0\ndeposit, 1, 4, 1.0\ndeposit, 1, 5, 1.0\n";
+        let throttled = || Throttled {
+            lines: tx.split_inclusive('\n'),
+            delay: Duration::from_millis(20),
+        };
+        let config: Config = Config {
+            timeout: Some(Duration::from_millis(50)),
+            process_order: ProcessOrder::File,
+            ..Config::default()
+        };
+
+        let result: LedgerResult = process(&config, throttled()).unwrap();
+        assert!(result.timed_out);
+        assert!(result.stats.rows < 5);
+        //The clients are the ones of the rows applied before the timeout
+        assert_eq!(
+            result.clients.first().map_or(Amount::ZERO, |c| c.total()),
+            Amount::from_scaled(result.stats.rows as i64 * Amount::SCALE)
+        );
+
+        //With enough time every row is applied
+        let config: Config = Config {
+            timeout: Some(Duration::from_secs(60)),
+            ..config
+        };
+        let result: LedgerResult = process(&config, throttled()).unwrap();
+        assert!(!result.timed_out);
+        assert_eq!(result.stats.rows, 5);
+
+        //In the default order the reading stops too, long before the whole input is read
+        let mut tx: String = String::from("type, client, tx, amount\n");
+        for id in 1..=50 {
+            tx.push_str(&format!("deposit, 1, {}, 1.0\n", id));
+        }
+        let config: Config = Config {
+            timeout: Some(Duration::from_millis(50)),
+            ..Config::default()
+        };
+        let start: Instant = Instant::now();
+        let result: LedgerResult = process(
+            &config,
+            Throttled {
+                lines: tx.split_inclusive('\n'),
+                delay: Duration::from_millis(20),
+            },
+        )
+        .unwrap();
+        assert!(result.timed_out);
+        assert_eq!(result.stats.rows, 0);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn timeline_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 10.0\ndeposit, 2, 2, 1.0\ndispute, 1, 1\nresolve, 1, 1\n";
+        let config: Config = Config {
+            timeline: Some("timeline.csv".to_string()),
+            ..Config::default()
+        };
+
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        let rows: Vec<(u32, u32, String, String, String)> = result
+            .timeline
+            .iter()
+            .filter(|row| row.client == 1)
+            .map(|row| {
+                (
+                    row.client,
+                    row.tx,
+                    row.available.to_string(),
+                    row.held.to_string(),
+                    row.total.to_string(),
+                )
+            })
+            .collect();
+        let row = |available: &str, held: &str| {
+            (
+                1,
+                1,
+                available.to_string(),
+                held.to_string(),
+                "10.0000".to_string(),
+            )
+        };
+        assert_eq!(
+            rows,
+            [
+                row("10.0000", "0.0000"),
+                row("0.0000", "10.0000"),
+                row("10.0000", "0.0000"),
+            ]
+        );
+        assert_eq!(result.timeline.len(), 4);
+
+        //Without the option there is no timeline
+        let result: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+        assert!(result.timeline.is_empty());
+    }
+
+    #[test]
+    fn on_transaction_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 10.0\nwithdrawal, 1, 2, 20.0\ndispute, 1, 1\ndispute, 1, 7\nwithdrawal, 2, 3, 1.0\n";
+        let mut calls: Vec<(u32, Outcome)> = Vec::new();
+
+        for process_order in [ProcessOrder::File, ProcessOrder::Tx] {
+            let config: Config = Config {
+                process_order,
+                ..Config::default()
+            };
+            calls.clear();
+            let result: LedgerResult =
+                process_all_with(&config, [tx.as_bytes()], |transaction, client, outcome| {
+                    assert_eq!(transaction.client, client.id());
+                    calls.push((transaction.tx, outcome));
+                })
+                .unwrap();
+            assert_eq!(result.stats.rows, 5);
+
+            let applied: usize = calls.iter().filter(|(_, o)| *o == Outcome::Applied).count();
+            assert_eq!(calls.len(), 5);
+            assert_eq!(applied, 2);
+        }
+    }
+
+    #[test]
+    fn coalesce_deposits_test() {
+        let mut tx: String = String::from("type, client, tx, amount\n");
+        for id in 1..=300 {
+            tx.push_str(&format!(
+                "deposit, {}, {}, 0.0{}\n",
+                id / 10 % 3,
+                id,
+                id % 10
+            ));
+            if id % 50 == 0 {
+                tx.push_str(&format!(
+                    "withdrawal, {}, {}, 0.5\n",
+                    id / 10 % 3,
+                    id + 1000
+                ));
+            }
+        }
+        let config: Config = Config {
+            coalesce_deposits: true,
+            ..Config::default()
+        };
+
+        let coalesced: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        let result: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+        assert_eq!(coalesced.report, result.report);
+        for (coalesced, client) in coalesced.clients.iter().zip(&result.clients) {
+            assert!(coalesced.same_balance(client));
+        }
+        assert!(coalesced.stats.rows < result.stats.rows);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn process_async_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 2, 1.0\ndeposit, 2, 1, 2.0\nwithdrawal, 1, 3, 0.5\n";
+        let (mut writer, reader) = tokio::io::duplex(16);
+
+        //The input arrives in small pieces while it's read
+        let feed = tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            writer.write_all(tx.as_bytes()).await.unwrap();
+        });
+        let result: LedgerResult = process_async(&Config::default(), reader).await.unwrap();
+        feed.await.unwrap();
+
+        let sync: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+        assert_eq!(result.clients, sync.clients);
+        assert_eq!(result.report, sync.report);
+        assert_eq!(result.stats.rows, 3);
+    }
+
+    #[test]
+    fn unmatched_test() {
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 10.0\ndispute, 1, 4\ndispute, 1, 1\nresolve, 2, 9\nchargeback, 3, 8\n";
+        let config: Config = Config {
+            report_unmatched: true,
+            process_order: ProcessOrder::File,
+            ..Config::default()
+        };
+
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        let lines: Vec<String> = result.unmatched.iter().map(|u| u.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "dispute tx 4 for client 1 references 4, not found",
+                "resolve tx 9 for client 2 references 9, not found",
+                "chargeback tx 8 for client 3 references 8, not found",
+            ]
+        );
+    }
+
+    #[test]
+    fn run_time_test() {
+        let time: RunTime = RunTime {
+            rows: 10,
+            elapsed: Duration::from_micros(1234),
+        };
+        let report: String = time.to_string();
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(lines[0], "Processed 10 rows in 0.001s");
+        assert!(lines[1].starts_with("Throughput "));
+        assert!(lines[1].ends_with(" rows/s"));
+
+        let time: RunTime = RunTime {
+            rows: 10,
+            elapsed: Duration::ZERO,
+        };
+        assert!(time.to_string().ends_with("Throughput unknown rows/s"));
+    }
+
+    #[test]
+    fn tx_order_dispute_before_deposit_test() {
+        let config: Config = Config {
+            process_order: ProcessOrder::Tx,
+            ..Config::default()
+        };
+        //The dispute of tx 1 comes first in the file, so also in tx order
+        let tx: &str =
+            "type, client, tx, amount\ndeposit, 1, 2, 1.0\ndispute, 1, 1\ndeposit, 1, 1, 3.0\n";
+
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        assert_eq!(
+            result.report,
+            "client,available,held,total,locked\n1,1.0000,3.0000,4.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn split_currencies_test() {
+        let config: Config = Config {
+            split_currencies: true,
+            ..Config::default()
+        };
+        let tx: &str = "type, client, tx, amount, currency\n\
+            deposit, 1, 1, 10.00, USD\n\
+            deposit, 1, 2, 5.00, EUR\n\
+            deposit, 1, 3, 1.0\n\
+            withdrawal, 1, 4, 2.00, EUR\n\
+            withdrawal, 1, 5, 4.00, EUR\n\
+            dispute, 1, 1\n\
+            dispute, 1, 2, 0, USD\n";
+
+        //A dispute without a currency finds the USD deposit, one in USD doesn't find the EUR deposit
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        assert_eq!(
+            result.report,
+            "client,currency,available,held,total,locked\n\
+             1,,1.0000,0.0000,1.0000,false\n\
+             1,EUR,3.00,0.00,3.00,false\n\
+             1,USD,0.00,10.00,10.00,false\n"
+        );
+        assert_eq!(result.clients.len(), 3);
+
+        //The withdrawal over the EUR balance is rejected, even if the client has more in USD
+        let (clients, report) = Client::process_transactions_with_report(
+            &Transaction::get_transactions_with(tx.to_string(), &config).unwrap(),
+            &config,
+        );
+        assert_eq!(clients.len(), 3);
+        assert_eq!(report.rejected_withdrawals.len(), 1);
+        assert_eq!(report.rejected_withdrawals[0].tx, 5);
+
+        //Without the option a client has a single balance
+        let result: LedgerResult = process(&Config::default(), tx.as_bytes()).unwrap();
+        assert_eq!(
+            result.report,
+            "client,available,held,total,locked\n1,1.00,15.00,16.00,false\n"
+        );
+    }
+
+    #[test]
+    fn parallel_test() {
+        let mut tx: String = String::from("type, client, tx, amount\n");
+        for id in 0..50_000u32 {
+            //Each block of ten rows is of one client and its disputes reference its deposits
+            let client: u32 = id / 10 % 700;
+            let row: String = match id % 10 {
+                0..=4 => format!("deposit, {}, {}, 2.5", client, id),
+                5 => format!("withdrawal, {}, {}, 4.0", client, id),
+                //Rejected for the funds, in every shard
+                6 => format!("withdrawal, {}, {}, 40.0", client, id),
+                7 => format!("dispute, {}, {}", client, id - 7),
+                8 if id % 30 == 8 => format!("chargeback, {}, {}", client, id - 8),
+                8 => format!("resolve, {}, {}", client, id - 8),
+                _ => format!("dispute, {}, {}", client, id - 6),
+            };
+            tx.push_str(&row);
+            tx.push('\n');
+        }
+
+        let run = |config: &Config| {
+            let mut steps: Vec<(u32, Outcome, Client)> = Vec::new();
+            let result: LedgerResult =
+                process_all_with(config, [tx.as_bytes()], |transaction, client, outcome| {
+                    steps.push((transaction.tx, outcome, *client))
+                })
+                .unwrap();
+            (result, steps)
+        };
+        let parallel: Config = Config {
+            parallel: true,
+            with_type_counts: true,
+            ..Config::default()
+        };
+        let sequential: Config = Config {
+            parallel: false,
+            ..parallel.clone()
+        };
+
+        assert!(independent(
+            &Transaction::get_transactions(tx.clone()).unwrap(),
+            &parallel
+        ));
+        let (expected, expected_steps) = run(&sequential);
+        //Several shards even on a machine with a single core
+        let (result, steps) = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap()
+            .install(|| run(&parallel));
+        assert_eq!(result.report, expected.report);
+        assert_eq!(result.clients, expected.clients);
+        assert_eq!(result.last_tx_id, expected.last_tx_id);
+        assert_eq!(result.stats.rows, 50_000);
+        assert_eq!(steps, expected_steps);
+        //The rejected withdrawals of the shards are in the order of the transactions
+        assert!(!result.process_report.rejected_withdrawals.is_empty());
+        assert_eq!(result.process_report, expected.process_report);
+        assert!(result.clients.iter().any(|client| client.is_locked()));
+
+        //A transfer ties two clients, so the run falls back to one thread with the same result
+        tx.push_str("transfer, 1, 50000, 1.0, 2\n");
+        let tx: String = tx.replace("amount\n", "amount, counterparty\n");
+        let expected: LedgerResult = process(&sequential, tx.as_bytes()).unwrap();
+        let result: LedgerResult = process(&parallel, tx.as_bytes()).unwrap();
+        assert_eq!(result.report, expected.report);
+    }
+
+    #[test]
+    fn opening_test() {
+        let path = std::env::temp_dir().join("accounts-cli-opening.csv");
+        std::fs::write(
+            &path,
+            "client,available,held,total,locked\n2,1.0000,0.0000,1.0000,true\n1,5.0000,1.5000,6.5000,false\n",
+        )
+        .unwrap();
+        let config: Config = Config {
+            opening: Some(path.to_str().unwrap().to_string()),
+            ..Config::default()
+        };
+        let expected: &str = "client,available,held,total,locked\n1,7.5000,1.5000,9.0000,false\n2,1.0000,0.0000,1.0000,true\n3,1.0000,0.0000,1.0000,false\n";
+
+        //The deposit adds to the opening balance, the locked client stays as it was
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 2.5\ndeposit, 2, 2, 4.0\ndeposit, 3, 3, 1.0\n";
+        let result: LedgerResult = process(&config, tx.as_bytes()).unwrap();
+        assert_eq!(result.report, expected);
+        assert_eq!(result.stats.rows, 3);
+        let mut output: Vec<u8> = Vec::new();
+        run_streaming(tx.as_bytes(), &mut output, &config).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        //The shards of --parallel are seeded too, and the seeded clients come first
+        for client_order in [ClientOrder::ClientId, ClientOrder::FirstSeen] {
+            let order = |parallel: bool| {
+                let config: Config = Config {
+                    parallel,
+                    client_order,
+                    ..config.clone()
+                };
+                process(&config, tx.as_bytes()).unwrap().report
+            };
+            assert_eq!(order(true), order(false));
+        }
+
+        //With --changed-only the seeded clients that didn't change are left out
+        let changed: Config = Config {
+            changed_only: true,
+            ..config.clone()
+        };
+        let result: LedgerResult = process(
+            &changed,
+            "type, client, tx, amount\ndeposit, 3, 3, 1.0\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.report,
+            "client,available,held,total,locked\n3,1.0000,0.0000,1.0000,false\n"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            process(&config, tx.as_bytes()),
+            Err(ProcessError::Io(e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+    }
+}
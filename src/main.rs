@@ -1,35 +1,201 @@
-pub mod clients;
-pub mod transactions;
-
 use std::env;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::process;
+use std::time::Instant;
+
+use accounts_cli::clients::timeline;
+use accounts_cli::config::models::Config;
+use accounts_cli::manifest::Manifest;
+use accounts_cli::transactions::models::TransactionError;
+use accounts_cli::{CheckReport, LedgerResult, ProcessError, RunTime};
+
+//Exit codes of the failures, listed in the README
+/// A row can't be read or doesn't pass the checks.
+const EXIT_INVALID_ROW: i32 = 1;
+/// The options can't be parsed.
+const EXIT_USAGE: i32 = 2;
+/// An input file or the opening balances don't exist.
+const EXIT_MISSING_FILE: i32 = 3;
+/// An input can't be read.
+const EXIT_UNREADABLE: i32 = 4;
+/// The output, the manifest or the timeline can't be written.
+const EXIT_OUTPUT: i32 = 5;
+
+/// Returns the exit code of a run that failed.
+fn exit_code(e: &ProcessError) -> i32 {
+    match e {
+        ProcessError::Transaction(e) => row_exit_code(e),
+        ProcessError::Csv(_) => EXIT_INVALID_ROW,
+        ProcessError::Io(e) => open_exit_code(e),
+        ProcessError::Output(_) => EXIT_OUTPUT,
+    }
+}
+
+/// Returns the exit code of a row that can't be read, the input itself may not be readable.
+fn row_exit_code(e: &TransactionError) -> i32 {
+    match e {
+        TransactionError::Read { .. } => EXIT_UNREADABLE,
+        _ => EXIT_INVALID_ROW,
+    }
+}
 
-use clients::models::Client;
-use transactions::models::Transaction;
+/// Returns the exit code of an input that can't be opened.
+fn open_exit_code(e: &io::Error) -> i32 {
+    match e.kind() {
+        io::ErrorKind::NotFound => EXIT_MISSING_FILE,
+        _ => EXIT_UNREADABLE,
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let path: &String = match args.get(1) {
-        Some(x) => x,
-        None => {
-            println!("Path for CSV file is needed");
-            return;
+    let mut config: Config = match Config::from_args(&args[1..]) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(EXIT_USAGE);
         }
     };
 
-    let tx: String = match fs::read_to_string(path) {
+    //RUST_LOG overrides the level of the --verbose option, the messages are written without a prefix
+    env_logger::Builder::new()
+        .format(|buf, record| writeln!(buf, "{}", record.args()))
+        .filter_level(if config.verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Warn
+        })
+        .parse_default_env()
+        .init();
+
+    if config.repl {
+        if let Err(e) = accounts_cli::repl::run(io::stdin().lock(), io::stdout().lock(), &config) {
+            println!("{}", e);
+            process::exit(EXIT_UNREADABLE);
+        }
+        return;
+    }
+
+    //Without a path the CSV is read from stdin, for pipelines
+    if config.paths.is_empty() {
+        config.paths.push(accounts_cli::STDIN_PATH.to_string());
+    }
+
+    let start: Instant = Instant::now();
+
+    //The manifest needs the whole output for its checksum and the timeline the balances of every
+    //transaction, so they are made by the path below, which also reads several files in file order with --stream
+    if config.stream
+        && !config.check
+        && config.manifest.is_none()
+        && config.timeline.is_none()
+        && config.paths.len() == 1
+    {
+        let file: Box<dyn Read> = match accounts_cli::open_input(&config.paths[0], &config) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Something went wrong reading the file {}", e);
+                process::exit(open_exit_code(&e));
+            }
+        };
+
+        let output: Box<dyn Write> = match &config.output {
+            Some(path) => match File::create(path) {
+                Ok(x) => Box::new(BufWriter::new(x)),
+                Err(e) => {
+                    println!("Something went wrong writing the output {} {}", path, e);
+                    process::exit(EXIT_OUTPUT);
+                }
+            },
+            None => Box::new(io::stdout().lock()),
+        };
+
+        match accounts_cli::run_streaming(file, output, &config) {
+            Ok(rows) if config.time => eprintln!(
+                "{}",
+                RunTime {
+                    rows,
+                    elapsed: start.elapsed()
+                }
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                println!("{}", e);
+                process::exit(exit_code(&e));
+            }
+        }
+        return;
+    }
+
+    let mut files: Vec<Box<dyn Read>> = Vec::new();
+    for path in &config.paths {
+        match accounts_cli::open_input(path, &config) {
+            Ok(x) => files.push(x),
+            Err(e) => {
+                println!("Something went wrong reading the file {} {}", path, e);
+                process::exit(open_exit_code(&e));
+            }
+        }
+    }
+
+    if config.check {
+        let report: CheckReport = accounts_cli::check_all(&config, files);
+        println!("{}", report);
+        if let Some(code) = report.errors.iter().map(row_exit_code).max() {
+            process::exit(code);
+        }
+        return;
+    }
+
+    let result: LedgerResult = match accounts_cli::process_all(&config, files) {
         Ok(x) => x,
         Err(e) => {
-            println!("Something went wrong reading the file {}", e);
-            return;
+            println!("{}", e);
+            process::exit(exit_code(&e));
         }
     };
 
-    let transactions: Vec<Transaction> = Transaction::get_transactions(tx);
+    match &config.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, &result.report) {
+                println!("Something went wrong writing the output {} {}", path, e);
+                process::exit(EXIT_OUTPUT);
+            }
+        }
+        None => {
+            if let Err(e) = io::stdout().lock().write_all(result.report.as_bytes()) {
+                println!("Error serializing {}", e);
+                process::exit(EXIT_OUTPUT);
+            }
+        }
+    }
 
-    let clients: Vec<Client> = Client::process_transactions(&transactions);
+    if let Some(manifest) = &config.manifest {
+        if let Err(e) = Manifest::new(&config, &args[1..], &result).write(manifest) {
+            println!("Something went wrong writing the manifest {}", e);
+            process::exit(EXIT_OUTPUT);
+        }
+    }
+
+    if let Some(path) = &config.timeline {
+        let written: Result<(), csv::Error> = File::create(path)
+            .map_err(csv::Error::from)
+            .and_then(|file| timeline::write_timeline(&result.timeline, BufWriter::new(file)));
+        if let Err(e) = written {
+            println!("Something went wrong writing the timeline {}", e);
+            process::exit(EXIT_OUTPUT);
+        }
+    }
 
-    let data: String = Client::clients_to_csv(clients);
-    println!("{}", data);
+    if config.time {
+        eprintln!(
+            "{}",
+            RunTime {
+                rows: result.stats.rows,
+                elapsed: start.elapsed()
+            }
+        );
+    }
 }
@@ -0,0 +1,115 @@
+use super::config::models::Config;
+use super::LedgerResult;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Description of a run written with `--manifest`, to trace an output back to its input and options.
+/// # Examples
+/// ```
+/// # use accounts_cli::config::models::Config;
+/// # use accounts_cli::manifest::Manifest;
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+/// let result = accounts_cli::process(&Config::default(), tx.as_bytes()).unwrap();
+///
+/// let manifest: Manifest = Manifest::new(&Config::default(), &[], &result);
+/// assert_eq!(manifest.rows, 1);
+/// assert_eq!(manifest.clients, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Manifest {
+    /// Paths of the input files.
+    pub inputs: Vec<String>,
+    /// Rows applied.
+    pub rows: u64,
+    /// Clients in the output.
+    pub clients: usize,
+    /// Command line options of the run, without the input path.
+    pub options: Vec<String>,
+    /// Version of the crate that made the output.
+    pub version: &'static str,
+    /// SHA-256 of the output, in hex.
+    pub output_sha256: String,
+}
+
+impl Manifest {
+    /// Describe the run of the [`LedgerResult`], made with the config parsed from `args`.
+    pub fn new(config: &Config, args: &[String], result: &LedgerResult) -> Self {
+        let inputs: Vec<String> = config.paths.clone();
+        let options: Vec<String> = args
+            .iter()
+            .filter(|arg| !inputs.contains(arg))
+            .cloned()
+            .collect();
+
+        Self {
+            inputs,
+            rows: result.stats.rows,
+            clients: result.clients.len(),
+            options,
+            version: env!("CARGO_PKG_VERSION"),
+            output_sha256: format!("{:x}", Sha256::digest(result.report.as_bytes())),
+        }
+    }
+
+    /// Write the manifest as indented JSON into the file at the path, replacing it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`io::Error`] if the file can't be created or written.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let mut file: BufWriter<File> = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_test() {
+        let args: Vec<String> = ["--format", "json", "tx.csv"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let config: Config = Config::from_args(&args).unwrap();
+        let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, 2, 2, 2.0\n";
+        let result: LedgerResult = super::super::process(&config, tx.as_bytes()).unwrap();
+
+        let manifest: Manifest = Manifest::new(&config, &args, &result);
+        assert_eq!(manifest.inputs, ["tx.csv"]);
+        assert_eq!(manifest.rows, 2);
+        assert_eq!(manifest.clients, 2);
+        assert_eq!(manifest.options, ["--format", "json"]);
+        assert_eq!(manifest.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            manifest.output_sha256,
+            format!("{:x}", Sha256::digest(result.report.as_bytes()))
+        );
+        assert_eq!(manifest.output_sha256.len(), 64);
+
+        let path = std::env::temp_dir().join("accounts-cli-manifest.json");
+        let path: &str = path.to_str().unwrap();
+        manifest.write(path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        for field in [
+            "inputs",
+            "rows",
+            "clients",
+            "options",
+            "version",
+            "output_sha256",
+        ] {
+            assert!(json.get(field).is_some(), "missing {}", field);
+        }
+        assert_eq!(json["options"][1], "json");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,363 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+/// An amount of money with four decimal places, kept as a whole number of ten-thousandths.
+/// Unlike a float the sums are exact, adding `0.1` ten times is exactly `1.0000`.
+/// # Examples
+/// ```
+/// # use accounts_cli::money::Amount;
+/// let amount: Amount = "1.5".parse().unwrap();
+/// assert_eq!((amount + amount).to_string(), "3.0000");
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(i64);
+
+/// Error of an amount that can't be parsed.
+#[derive(Debug, PartialEq)]
+pub enum ParseAmountError {
+    /// The text is not a decimal number.
+    Invalid,
+    /// The number doesn't fit in an [`Amount`].
+    Overflow,
+}
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseAmountError::Invalid => write!(f, "not a decimal number"),
+            ParseAmountError::Overflow => write!(f, "the amount is too large"),
+        }
+    }
+}
+
+impl std::error::Error for ParseAmountError {}
+
+impl Amount {
+    /// Number of decimal places of an amount.
+    pub const DECIMALS: usize = 4;
+    /// Ten-thousandths in a unit.
+    pub const SCALE: i64 = 10_000;
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(i64::MAX);
+
+    /// Create an amount from a whole number of ten-thousandths.
+    pub const fn from_scaled(scaled: i64) -> Amount {
+        Amount(scaled)
+    }
+
+    /// Returns the amount as a whole number of ten-thousandths.
+    pub const fn scaled(self) -> i64 {
+        self.0
+    }
+
+    /// Returns the sum, or [`None`] if it overflows.
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    /// Returns the difference, or [`None`] if it overflows.
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Returns the amount multiplied by the factor, rounded half away from zero to four decimal places,
+    /// or [`None`] if it overflows.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::money::Amount;
+    /// let amount: Amount = "1234.5".parse().unwrap();
+    /// let factor: Amount = "0.001".parse().unwrap();
+    /// assert_eq!(amount.checked_mul(factor).unwrap().to_string(), "1.2345");
+    /// ```
+    pub fn checked_mul(self, factor: Amount) -> Option<Amount> {
+        let product: i128 = i128::from(self.0) * i128::from(factor.0);
+        let scale: i128 = i128::from(Amount::SCALE);
+        let half: i128 = if product < 0 { -scale / 2 } else { scale / 2 };
+
+        i64::try_from((product + half) / scale).ok().map(Amount)
+    }
+
+    /// Write the amount with fewer decimal places, rounded half away from zero, like `1.26` for `1.2550`
+    /// with `2`. Decimal places above four are written as four.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::money::Amount;
+    /// let amount: Amount = "-1.255".parse().unwrap();
+    /// assert_eq!(amount.to_string_with(2), "-1.26");
+    /// assert_eq!(amount.to_string_with(0), "-1");
+    /// ```
+    pub fn to_string_with(self, decimals: usize) -> String {
+        let decimals: u32 = decimals.min(Amount::DECIMALS) as u32;
+        let step: u64 = 10u64.pow(Amount::DECIMALS as u32 - decimals);
+        let scaled: u64 = self.0.unsigned_abs();
+        let rounded: u128 = (u128::from(scaled) + u128::from(step / 2)) / u128::from(step);
+
+        //A negative amount rounded to zero is written without sign
+        let sign: &str = if self.0 < 0 && rounded > 0 { "-" } else { "" };
+        let unit: u128 = 10u128.pow(decimals);
+        match decimals {
+            0 => format!("{}{}", sign, rounded),
+            _ => format!(
+                "{}{}.{:0width$}",
+                sign,
+                rounded / unit,
+                rounded % unit,
+                width = decimals as usize
+            ),
+        }
+    }
+}
+
+/// Decimal places of the minor unit of common currencies, by their ISO 4217 code.
+/// The currencies not in the list are written with the four decimal places of an [`Amount`].
+pub const CURRENCY_DECIMALS: [(&str, usize); 14] = [
+    ("AUD", 2),
+    ("BHD", 3),
+    ("CAD", 2),
+    ("CHF", 2),
+    ("CNY", 2),
+    ("EUR", 2),
+    ("GBP", 2),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("MXN", 2),
+    ("USD", 2),
+    ("VND", 0),
+];
+
+/// Returns the decimal places of the currency in [`CURRENCY_DECIMALS`], [`None`] for other codes.
+/// # Examples
+/// ```
+/// # use accounts_cli::money::currency_decimals;
+/// assert_eq!(currency_decimals("JPY"), Some(0));
+/// assert_eq!(currency_decimals("XYZ"), None);
+/// ```
+pub fn currency_decimals(code: &str) -> Option<usize> {
+    CURRENCY_DECIMALS
+        .iter()
+        .find(|(currency, _)| *currency == code)
+        .map(|(_, decimals)| *decimals)
+}
+
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    /// Parse a decimal number like `1`, `-2.5` or `.75`.
+    /// Decimal places after the fourth are rounded half away from zero.
+    fn from_str(s: &str) -> Result<Amount, ParseAmountError> {
+        let (negative, digits): (bool, &str) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (units, decimals): (&str, &str) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (units.is_empty() && decimals.is_empty()) || !is_digits(units) || !is_digits(decimals) {
+            return Err(ParseAmountError::Invalid);
+        }
+
+        let mut scaled: i64 = 0;
+        for digit in units.bytes() {
+            scaled = scaled
+                .checked_mul(10)
+                .and_then(|s| s.checked_add(i64::from(digit - b'0')))
+                .ok_or(ParseAmountError::Overflow)?;
+        }
+        scaled = scaled
+            .checked_mul(Amount::SCALE)
+            .ok_or(ParseAmountError::Overflow)?;
+
+        let mut place: i64 = Amount::SCALE;
+        for digit in decimals.bytes().take(Amount::DECIMALS) {
+            place /= 10;
+            scaled += i64::from(digit - b'0') * place;
+        }
+        if decimals.as_bytes().get(Amount::DECIMALS) >= Some(&b'5') {
+            scaled = scaled.checked_add(1).ok_or(ParseAmountError::Overflow)?;
+        }
+
+        Ok(Amount(if negative { -scaled } else { scaled }))
+    }
+}
+
+impl fmt::Display for Amount {
+    /// Write the amount with four decimal places, like `-2.5000`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign: &str = if self.0 < 0 { "-" } else { "" };
+        let scaled: u64 = self.0.unsigned_abs();
+        let scale: u64 = Amount::SCALE as u64;
+
+        write!(f, "{}{}.{:04}", sign, scaled / scale, scaled % scale)
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, other: Amount) -> Amount {
+        Amount(self.0 + other.0)
+    }
+}
+
+impl Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, other: Amount) -> Amount {
+        Amount(self.0 - other.0)
+    }
+}
+
+impl Neg for Amount {
+    type Output = Amount;
+
+    fn neg(self) -> Amount {
+        Amount(-self.0)
+    }
+}
+
+impl AddAssign for Amount {
+    fn add_assign(&mut self, other: Amount) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Amount {
+    fn sub_assign(&mut self, other: Amount) {
+        self.0 -= other.0;
+    }
+}
+
+/// Amounts are written as text with four decimal places, so no precision is lost in the output.
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        let amount: String = String::deserialize(deserializer)?;
+        amount.trim().parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(amount("1"), Amount::from_scaled(10_000));
+        assert_eq!(amount("1.5"), Amount::from_scaled(15_000));
+        assert_eq!(amount("-2.25"), Amount::from_scaled(-22_500));
+        assert_eq!(amount("+0.0001"), Amount::from_scaled(1));
+        assert_eq!(amount(".75"), Amount::from_scaled(7_500));
+        assert_eq!(amount("3."), Amount::from_scaled(30_000));
+        assert_eq!(amount("007"), Amount::from_scaled(70_000));
+
+        assert_eq!("".parse::<Amount>(), Err(ParseAmountError::Invalid));
+        assert_eq!(".".parse::<Amount>(), Err(ParseAmountError::Invalid));
+        assert_eq!("-".parse::<Amount>(), Err(ParseAmountError::Invalid));
+        assert_eq!("1.2.3".parse::<Amount>(), Err(ParseAmountError::Invalid));
+        assert_eq!("1e5".parse::<Amount>(), Err(ParseAmountError::Invalid));
+        assert_eq!(" 1".parse::<Amount>(), Err(ParseAmountError::Invalid));
+        assert_eq!(
+            "99999999999999999999".parse::<Amount>(),
+            Err(ParseAmountError::Overflow)
+        );
+    }
+
+    #[test]
+    fn rounding_test() {
+        assert_eq!(amount("0.00004"), Amount::ZERO);
+        assert_eq!(amount("0.00005"), Amount::from_scaled(1));
+        assert_eq!(amount("1.23456789"), Amount::from_scaled(12_346));
+        assert_eq!(amount("-0.00005"), Amount::from_scaled(-1));
+        assert_eq!(amount("0.99995"), amount("1"));
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let mut total: Amount = Amount::ZERO;
+        for _ in 0..10 {
+            total += amount("0.1");
+        }
+        assert_eq!(total, amount("1"));
+
+        assert_eq!(amount("1.5") + amount("2.25"), amount("3.75"));
+        assert_eq!(amount("1.5") - amount("2.25"), amount("-0.75"));
+        assert_eq!(-amount("1.5"), amount("-1.5"));
+
+        total -= amount("0.3");
+        assert_eq!(total, amount("0.7"));
+        assert!((amount("1") - amount("1.0001")).is_negative());
+
+        assert_eq!(Amount::MAX.checked_add(amount("0.0001")), None);
+        assert_eq!((-Amount::MAX).checked_sub(amount("1")), None);
+        assert_eq!(amount("1").checked_sub(amount("1")), Some(Amount::ZERO));
+    }
+
+    #[test]
+    fn to_string_with_test() {
+        assert_eq!(amount("1.2345").to_string_with(4), "1.2345");
+        assert_eq!(amount("1.2345").to_string_with(8), "1.2345");
+        assert_eq!(amount("1.2345").to_string_with(3), "1.235");
+        assert_eq!(amount("1.2344").to_string_with(3), "1.234");
+        assert_eq!(amount("9.995").to_string_with(2), "10.00");
+        assert_eq!(amount("2.5").to_string_with(0), "3");
+        assert_eq!(amount("-0.004").to_string_with(2), "0.00");
+        assert_eq!(Amount::MAX.to_string_with(0), "922337203685478");
+    }
+
+    #[test]
+    fn checked_mul_test() {
+        assert_eq!(amount("2.5").checked_mul(amount("2")), Some(amount("5")));
+        assert_eq!(
+            amount("1000").checked_mul(amount("0.001")),
+            Some(amount("1"))
+        );
+        assert_eq!(
+            amount("0.05").checked_mul(amount("0.001")),
+            Some(amount("0.0001"))
+        );
+        assert_eq!(
+            amount("0.04").checked_mul(amount("0.001")),
+            Some(Amount::ZERO)
+        );
+        assert_eq!(
+            amount("-0.05").checked_mul(amount("0.001")),
+            Some(amount("-0.0001"))
+        );
+        assert_eq!(Amount::MAX.checked_mul(amount("2")), None);
+    }
+
+    #[test]
+    fn display_test() {
+        assert_eq!(amount("1").to_string(), "1.0000");
+        assert_eq!(amount("-2.5").to_string(), "-2.5000");
+        assert_eq!(amount("-0.0001").to_string(), "-0.0001");
+        assert_eq!(amount("1234.5678").to_string(), "1234.5678");
+        assert_eq!(Amount::ZERO.to_string(), "0.0000");
+        assert_eq!(
+            Amount::from_scaled(i64::MIN).to_string(),
+            "-922337203685477.5808"
+        );
+        assert_eq!(serde_json::to_string(&amount("0.5")).unwrap(), "\"0.5000\"");
+        assert_eq!(
+            serde_json::from_str::<Amount>("\" 2.5 \"").unwrap(),
+            amount("2.5")
+        );
+    }
+}
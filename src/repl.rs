@@ -0,0 +1,85 @@
+use super::clients::ledger::ClientLedger;
+use super::config::models::Config;
+use std::io::{self, BufRead, Write};
+
+/// Read transactions from the input one line at a time, apply them to an in-memory [`ClientLedger`]
+/// and write the new balance of the client after each line.
+/// The lines are CSV records without header, like `deposit, 1, 1, 1.0`. Besides them:
+/// ```text
+/// print <id>  write the balance of a client
+/// dump        write the balance of every client
+/// ```
+/// A line that can't be read is reported and the session goes on.
+///
+/// # Errors
+///
+/// Returns the [`io::Error`] if the input can't be read or the output can't be written.
+/// # Examples
+/// ```
+/// use accounts_cli::config::models::Config;
+///
+/// let input: &str = "deposit, 1, 1, 2.0\nprint 1\n";
+/// let mut output: Vec<u8> = Vec::new();
+///
+/// accounts_cli::repl::run(input.as_bytes(), &mut output, &Config::default()).unwrap();
+/// ```
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W, config: &Config) -> io::Result<()> {
+    let mut ledger: ClientLedger = ClientLedger::with_config(config);
+
+    for (index, line) in input.lines().enumerate() {
+        let line: String = line?;
+        let command: Vec<&str> = line.split_whitespace().collect();
+
+        match command.as_slice() {
+            [] => {}
+            ["dump"] => {
+                for client in ledger.clients() {
+                    writeln!(output, "{}", client)?;
+                }
+            }
+            ["print", id] => match id.parse::<u32>().ok().and_then(|id| ledger.get(id)) {
+                Some(client) => writeln!(output, "{}", client)?,
+                None => writeln!(output, "Client {} not found", id)?,
+            },
+            _ => match ledger.apply_csv_line(&line, index as u64 + 1) {
+                Ok(client) => writeln!(output, "{}", client)?,
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+        }
+
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_test() {
+        let input: &str = "deposit, 1, 1, 10.0\n\
+                           deposit, 2, 2, 3.0\n\
+                           dispute, 1, 1\n\
+                           withdrawal, 1, x, 1.0\n\
+                           print 1\n\
+                           print 3\n\
+                           dump\n";
+        let mut output: Vec<u8> = Vec::new();
+
+        run(input.as_bytes(), &mut output, &Config::default()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client 1: available=10.0000 held=0.0000 total=10.0000 locked=false\n\
+             client 2: available=3.0000 held=0.0000 total=3.0000 locked=false\n\
+             client 1: available=0.0000 held=10.0000 total=10.0000 locked=false\n\
+             Line 4: invalid tx ID \"x\"\n\
+             client 1: available=0.0000 held=10.0000 total=10.0000 locked=false\n\
+             Client 3 not found\n\
+             client 1: available=0.0000 held=10.0000 total=10.0000 locked=false\n\
+             client 2: available=3.0000 held=0.0000 total=3.0000 locked=false\n"
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use super::super::config::models::Config;
+use super::models::Transaction;
+use std::ops::Range;
+
+/// Byte ranges of the columns in a fixed-width (non-delimited) file.
+/// The spec is a list of `column=start-end` pairs, zero-based and with the end excluded:
+/// ```text
+/// type=0-10,client=10-15,tx=15-20,amount=20-30
+/// ```
+/// The `amount` column is optional, every other column is required.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedWidthSpec {
+    pub tx_type: Range<usize>,
+    pub client: Range<usize>,
+    pub tx: Range<usize>,
+    pub amount: Option<Range<usize>>,
+}
+
+impl FixedWidthSpec {
+    /// Parse a spec like `type=0-10,client=10-15,tx=15-20,amount=20-30`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if a column is unknown, a range is invalid or a required column is missing.
+    pub fn parse(spec: &str) -> Result<FixedWidthSpec, String> {
+        let mut tx_type: Option<Range<usize>> = None;
+        let mut client: Option<Range<usize>> = None;
+        let mut tx: Option<Range<usize>> = None;
+        let mut amount: Option<Range<usize>> = None;
+
+        for column in spec.split(',') {
+            let (name, range) = column
+                .split_once('=')
+                .ok_or(format!("Invalid fixed-width column {}", column))?;
+            let range: Range<usize> = FixedWidthSpec::parse_range(range)
+                .ok_or(format!("Invalid byte range for column {}", name))?;
+
+            match name.trim() {
+                "type" => tx_type = Some(range),
+                "client" => client = Some(range),
+                "tx" => tx = Some(range),
+                "amount" => amount = Some(range),
+                other => return Err(format!("Unknown fixed-width column {}", other)),
+            }
+        }
+
+        Ok(FixedWidthSpec {
+            tx_type: tx_type.ok_or("Fixed-width spec needs a type column")?,
+            client: client.ok_or("Fixed-width spec needs a client column")?,
+            tx: tx.ok_or("Fixed-width spec needs a tx column")?,
+            amount,
+        })
+    }
+
+    fn parse_range(range: &str) -> Option<Range<usize>> {
+        let (start, end) = range.trim().split_once('-')?;
+        let start: usize = start.parse().ok()?;
+        let end: usize = end.parse().ok()?;
+
+        if start < end {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+
+    /// Return the transactions from a fixed-width file, one record per line.
+    /// Blank lines are skipped and, like [`Transaction::get_transactions`], the result is sorted by the ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a field can't be parsed.
+    /// # Examples
+    /// ```
+    /// # #![allow(deprecated)]
+    /// # use accounts_cli::transactions::fixed_width::FixedWidthSpec;
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// # let tx: String = String::from("deposit       1    3       2.0");
+    /// let spec: FixedWidthSpec = FixedWidthSpec::parse("type=0-10,client=10-15,tx=15-20,amount=20-30").unwrap();
+    /// let transactions: Vec<Transaction> = spec.get_transactions(tx);
+    /// ```
+    #[deprecated(
+        note = "read the input with the spec in the Config, like Transaction::get_transactions_with, which returns the errors"
+    )]
+    pub fn get_transactions(&self, tx: String) -> Vec<Transaction> {
+        let config: Config = Config {
+            fixed_width: Some(self.clone()),
+            ..Config::default()
+        };
+
+        match Transaction::get_transactions_with(tx, &config) {
+            Ok(transactions) => transactions,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Return the records of a fixed-width file, one per line, with the line number as position.
+    /// Blank lines are skipped. The records have the same layout as the CSV ones, so they are read
+    /// into transactions by [`TransactionRecords`](super::records::TransactionRecords) when the
+    /// [`Config`] has the spec, with the error and line of each bad record.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::config::models::Config;
+    /// # use accounts_cli::transactions::fixed_width::FixedWidthSpec;
+    /// # use accounts_cli::transactions::models::DepositRefs;
+    /// # use accounts_cli::transactions::records::TransactionRecords;
+    /// let tx: &str = "deposit       1    3       2.0\n\ndeposit       1    4        one";
+    /// let spec: FixedWidthSpec = FixedWidthSpec::parse("type=0-10,client=10-15,tx=15-20,amount=20-30").unwrap();
+    /// assert_eq!(spec.records(tx).count(), 2);
+    ///
+    /// let config: Config = Config {
+    ///     fixed_width: Some(spec),
+    ///     ..Config::default()
+    /// };
+    /// let mut refs: DepositRefs = DepositRefs::default();
+    /// let results: Vec<_> = TransactionRecords::new(tx.as_bytes(), &mut refs, &config)
+    ///     .unwrap()
+    ///     .collect();
+    /// assert!(results[0].is_ok());
+    /// assert!(results[1].is_err());
+    /// ```
+    pub fn records<'a>(&'a self, tx: &'a str) -> impl Iterator<Item = csv::StringRecord> + 'a {
+        tx.lines()
+            .enumerate()
+            .filter_map(move |(index, line)| self.record(line, index as u64 + 1))
+    }
+
+    /// Return the record of a single line, or [`None`] if the line is blank.
+    pub fn record(&self, line: &str, line_number: u64) -> Option<csv::StringRecord> {
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        let mut fields: Vec<&str> = vec![
+            FixedWidthSpec::field(line, &self.tx_type),
+            FixedWidthSpec::field(line, &self.client),
+            FixedWidthSpec::field(line, &self.tx),
+        ];
+
+        //A blank amount is the same as a missing column in the CSV
+        if let Some(amount) = &self.amount {
+            let amount: &str = FixedWidthSpec::field(line, amount);
+            if !amount.is_empty() {
+                fields.push(amount);
+            }
+        }
+
+        let mut position: csv::Position = csv::Position::new();
+        position.set_line(line_number);
+
+        let mut record: csv::StringRecord = csv::StringRecord::from(fields);
+        record.set_position(Some(position));
+        Some(record)
+    }
+
+    /// Cut a column from the line, a line shorter than the range gives what is left of it.
+    fn field<'a>(line: &'a str, range: &Range<usize>) -> &'a str {
+        let end: usize = range.end.min(line.len());
+        line.get(range.start..end).unwrap_or("").trim()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::money::Amount;
+    use super::super::models::TransactionType;
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_test() {
+        let spec: FixedWidthSpec =
+            FixedWidthSpec::parse("type=0-10,client=10-15,tx=15-20,amount=20-30").unwrap();
+
+        assert_eq!(spec.tx_type, 0..10);
+        assert_eq!(spec.amount, Some(20..30));
+
+        assert!(FixedWidthSpec::parse("type=0-10,client=10-15").is_err());
+        assert!(FixedWidthSpec::parse("type=10-0,client=10-15,tx=15-20").is_err());
+        assert!(FixedWidthSpec::parse("type=0-10,client=10-15,tx=15-20,memo=20-30").is_err());
+    }
+
+    #[test]
+    fn get_transactions_test() {
+        let config: Config = Config {
+            fixed_width: Some(
+                FixedWidthSpec::parse("type=0-10,client=10-15,tx=15-20,amount=20-30").unwrap(),
+            ),
+            ..Config::default()
+        };
+        let tx_string: String = String::from(
+            "deposit       1    3       2.0\nwithdrawal    1    4       1.5\ndispute       1    3\n",
+        );
+
+        let txs: Vec<Transaction> = vec![
+            Transaction::new(TransactionType::Deposit, 1, 3, amount("2.0")),
+            Transaction::new(TransactionType::Dispute, 1, 3, amount("0.0")),
+            Transaction::new(TransactionType::Withdrawal, 1, 4, amount("1.5")),
+        ];
+
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string.clone(), &config),
+            Ok(txs.clone())
+        );
+
+        //The deprecated reader gives the same transactions
+        #[allow(deprecated)]
+        let read: Vec<Transaction> = config
+            .fixed_width
+            .as_ref()
+            .unwrap()
+            .get_transactions(tx_string);
+        assert_eq!(read, txs);
+    }
+}
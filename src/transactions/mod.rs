@@ -1 +1,3 @@
+pub mod fixed_width;
 pub mod models;
+pub mod records;
@@ -1,37 +1,383 @@
+use super::super::config::models::{Config, DisputeKey, ProcessOrder, TimestampTiebreak};
+use super::super::money::Amount;
+use super::records::TransactionRecords;
 use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+use std::time::Instant;
+
+/// Error found while reading the transactions of a file.
+/// Every error has the line of the record in the file, or `0` for a record without position.
+#[derive(Debug, PartialEq)]
+pub enum TransactionError {
+    /// The record can't be read from the CSV.
+    Csv { line: u64, message: String },
+    /// The input can't be read, like a directory or a truncated gzip file.
+    Read { line: u64, message: String },
+    /// A required column is missing from the record.
+    MissingColumn { line: u64, column: &'static str },
+    /// The client ID is not a number.
+    InvalidClient { line: u64, value: String },
+    /// The client or counterparty ID is above the `--max-client-id`.
+    ClientIdTooLarge { line: u64, client: u32, max: u32 },
+    /// The tx ID is not a number.
+    InvalidTx { line: u64, value: String },
+    /// The amount is not a number.
+    InvalidAmount { line: u64, value: String },
+    /// A deposit, withdrawal or transfer has an amount below zero.
+    NegativeAmount { line: u64, tx: u32 },
+    /// The amount is larger than allowed by `--max-amount`, positive or negative.
+    AmountTooLarge { line: u64, tx: u32, max: Amount },
+    /// The amount has more decimal places than allowed by `--max-input-decimals`.
+    TooManyDecimals {
+        line: u64,
+        amount: String,
+        max: usize,
+    },
+    /// Two deposits share the `ref` used to find them with `--dispute-key ref`.
+    DuplicateRef { line: u64, reference: String },
+    /// Two deposits, withdrawals or transfers share the tx ID, only an error with `--strict-tx-ids`.
+    DuplicateTx { line: u64, tx: u32 },
+    /// The header doesn't have the expected columns, only an error with `--strict-header`.
+    InvalidHeader { line: u64, message: String },
+    /// The timestamp is not a number of seconds.
+    InvalidTimestamp { line: u64, value: String },
+    /// The type is not one of the [`TRANSACTION_TYPES`], nor a `--type-alias` of one.
+    InvalidType { line: u64, value: String },
+    /// The amount uses the other decimal separator than the one chosen with `--decimal-comma`.
+    DecimalSeparator {
+        line: u64,
+        amount: String,
+        expected: char,
+    },
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Csv { line, message } => write!(f, "Line {}: {}", line, message),
+            TransactionError::Read { line, message } => {
+                write!(f, "Line {}: can't read the input, {}", line, message)
+            }
+            TransactionError::MissingColumn { line, column } => {
+                write!(f, "Line {}: missing column {}", line, column)
+            }
+            TransactionError::InvalidType { line, value } => {
+                write!(f, "Line {}: invalid transaction type {:?}", line, value)
+            }
+            TransactionError::InvalidClient { line, value } => {
+                write!(f, "Line {}: invalid client ID {:?}", line, value)
+            }
+            TransactionError::ClientIdTooLarge { line, client, max } => {
+                write!(f, "Line {}: client ID {} is above {}", line, client, max)
+            }
+            TransactionError::InvalidTx { line, value } => {
+                write!(f, "Line {}: invalid tx ID {:?}", line, value)
+            }
+            TransactionError::InvalidAmount { line, value } => {
+                write!(f, "Line {}: invalid amount {:?}", line, value)
+            }
+            TransactionError::NegativeAmount { line, tx } => {
+                write!(f, "Line {}: tx {} has a negative amount", line, tx)
+            }
+            TransactionError::AmountTooLarge { line, tx, max } => {
+                write!(
+                    f,
+                    "Line {}: tx {} has an amount larger than {}",
+                    line, tx, max
+                )
+            }
+            TransactionError::TooManyDecimals { line, amount, max } => write!(
+                f,
+                "Line {}: amount {} has more than {} decimal places",
+                line, amount, max
+            ),
+            TransactionError::DuplicateRef { line, reference } => {
+                write!(
+                    f,
+                    "Line {}: ref {} is used by another deposit",
+                    line, reference
+                )
+            }
+            TransactionError::DuplicateTx { line, tx } => {
+                write!(
+                    f,
+                    "Line {}: tx ID {} is used by another transaction",
+                    line, tx
+                )
+            }
+            TransactionError::InvalidHeader { line, message } => {
+                write!(f, "Line {}: invalid header, {}", line, message)
+            }
+            TransactionError::InvalidTimestamp { line, value } => {
+                write!(f, "Line {}: invalid timestamp {:?}", line, value)
+            }
+            TransactionError::DecimalSeparator {
+                line,
+                amount,
+                expected,
+            } => write!(
+                f,
+                "Line {}: amount {:?} uses the decimal separator {:?}, expected {:?}",
+                line,
+                amount,
+                if *expected == ',' { '.' } else { ',' },
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+impl From<csv::Error> for TransactionError {
+    fn from(e: csv::Error) -> Self {
+        let line: u64 = e.position().map_or(0, |p| p.line());
+        match e.kind() {
+            csv::ErrorKind::Io(e) => TransactionError::Read {
+                line,
+                message: e.to_string(),
+            },
+            _ => TransactionError::Csv {
+                line,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Position of the columns in the records of a file, found by name in the header in any order.
+/// Records without a header, like fixed-width ones, have the required columns in the order of
+/// [`EXPECTED_COLUMNS`] and none of the optional ones, the [`Default`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns {
+    /// The `type` column.
+    pub tx_type: usize,
+    /// The `client` column.
+    pub client: usize,
+    /// The `tx` column.
+    pub tx: usize,
+    /// The `amount` column, optional in the rows of disputes, resolves and chargebacks.
+    pub amount: usize,
+    /// The `ref` column, an external reference that disputes can use instead of the tx ID.
+    pub reference: Option<usize>,
+    /// The `timestamp` column, used by `--process-order timestamp`.
+    pub timestamp: Option<usize>,
+    /// The `counterparty` column, the client that receives a transfer.
+    pub counterparty: Option<usize>,
+    /// The `currency` column, the code that chooses the decimal places of the balances of the client.
+    pub currency: Option<usize>,
+}
+
+/// Names of the required columns, in their order for records without a header.
+pub const EXPECTED_COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Names of the other columns allowed in the header.
+pub const OPTIONAL_COLUMNS: [&str; 4] = ["ref", "timestamp", "counterparty", "currency"];
+
+/// Types of transaction the ledger applies, the ones `--type-alias` can map to.
+pub const TRANSACTION_TYPES: [&str; 7] = [
+    "deposit",
+    "withdrawal",
+    "dispute",
+    "resolve",
+    "chargeback",
+    "transfer",
+    "fee",
+];
+
+/// Type of a transaction, read from the `type` column.
+/// The names are lowercase, a type with other case like `Deposit` is a bad row.
+/// # Examples
+/// ```
+/// # use accounts_cli::transactions::models::TransactionType;
+/// assert_eq!(TransactionType::parse("deposit"), Ok(TransactionType::Deposit));
+/// assert!(TransactionType::parse("Deposit").is_err());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Transfer,
+    /// Takes the amount from available even without the funds, unlike a withdrawal.
+    Fee,
+}
+
+impl TransactionType {
+    /// Parse the name of a type, one of the [`TRANSACTION_TYPES`].
+    pub fn parse(tx_type: &str) -> Result<TransactionType, String> {
+        match tx_type {
+            "deposit" => Ok(TransactionType::Deposit),
+            "withdrawal" => Ok(TransactionType::Withdrawal),
+            "dispute" => Ok(TransactionType::Dispute),
+            "resolve" => Ok(TransactionType::Resolve),
+            "chargeback" => Ok(TransactionType::Chargeback),
+            "transfer" => Ok(TransactionType::Transfer),
+            "fee" => Ok(TransactionType::Fee),
+            other => Err(format!("Unknown transaction type {}", other)),
+        }
+    }
+
+    /// Returns the name of the type, like it's written in the `type` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Transfer => "transfer",
+            TransactionType::Fee => "fee",
+        }
+    }
+}
+
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            tx_type: 0,
+            client: 1,
+            tx: 2,
+            amount: 3,
+            reference: None,
+            timestamp: None,
+            counterparty: None,
+            currency: None,
+        }
+    }
+}
+
+impl Columns {
+    /// Find the columns in the header of the CSV by name. A required column that is not found keeps
+    /// its position of the [`Default`], see [`Columns::read_headers`] to reject the header instead.
+    pub fn from_headers(headers: &csv::StringRecord) -> Columns {
+        let defaults: Columns = Columns::default();
+        let find = |name: &str| headers.iter().position(|h| h.trim() == name);
+
+        Columns {
+            tx_type: find("type").unwrap_or(defaults.tx_type),
+            client: find("client").unwrap_or(defaults.client),
+            tx: find("tx").unwrap_or(defaults.tx),
+            amount: find("amount").unwrap_or(defaults.amount),
+            reference: headers.iter().position(|h| h.trim() == "ref"),
+            timestamp: headers.iter().position(|h| h.trim() == "timestamp"),
+            counterparty: headers.iter().position(|h| h.trim() == "counterparty"),
+            currency: headers.iter().position(|h| h.trim() == "currency"),
+        }
+    }
+
+    /// Check the header against the [`EXPECTED_COLUMNS`], then find the columns by name.
+    /// A missing required column stops the run, like a file without a header whose first row would be
+    /// read as one. The extra columns are warned in stderr, or an error if `strict_header` is set.
+    /// An empty header, from an input without a single line like a zero-byte file, is not checked,
+    /// the input has no records and its output is the empty one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TransactionError::InvalidHeader`] with every problem if a required column is missing,
+    /// or if there is any problem and `strict_header` is set.
+    pub fn read_headers(
+        headers: &csv::StringRecord,
+        config: &Config,
+    ) -> Result<Columns, TransactionError> {
+        let problems: Vec<String> = Columns::check_headers(headers);
+        let missing: bool = EXPECTED_COLUMNS
+            .iter()
+            .any(|expected| !headers.iter().any(|h| h.trim() == *expected));
+
+        if !problems.is_empty() && !headers.is_empty() {
+            if config.strict_header || missing {
+                return Err(TransactionError::InvalidHeader {
+                    line: headers.position().map_or(1, |p| p.line()),
+                    message: problems.join(", "),
+                });
+            }
+
+            for problem in &problems {
+                log::warn!("Warning: header {}", problem);
+            }
+        }
+
+        Ok(Columns::from_headers(headers))
+    }
+
+    /// Returns the problems of the header: expected columns that are missing
+    /// and extra columns that are neither expected nor optional ones.
+    pub fn check_headers(headers: &csv::StringRecord) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+
+        for expected in EXPECTED_COLUMNS {
+            if !headers.iter().any(|h| h.trim() == expected) {
+                problems.push(format!("missing column {:?}", expected));
+            }
+        }
+
+        for name in headers.iter().map(|h| h.trim()) {
+            if !EXPECTED_COLUMNS.contains(&name) && !OPTIONAL_COLUMNS.contains(&name) {
+                problems.push(format!("extra column {:?}", name));
+            }
+        }
+
+        problems
+    }
+}
+
 /// Implementation for basic transactions in CSV
 /// This is focused on processing CSV files
-/// ```
+/// ```text
 /// type,      client,  tx, amount
 /// deposit,        1,   1,    1.0
 /// withdrawal,     1,   4,    1.5
 /// ```
+/// An optional `ref` column after those gives an external reference for `--dispute-key ref`,
+/// an optional `timestamp` column, in seconds, the time for `--process-order timestamp`,
+/// a `counterparty` column with the client that receives a `transfer`, required for those,
+/// and a `currency` column with the code of the currency of the client, like `USD`.
 ///
 /// # Example
 /// You can create a transaction from a [`csv::StringRecord`] with [`Transaction::new_from_csv`]:
 /// ```
+/// # use accounts_cli::transactions::models::Transaction;
+/// # let tx: String = String::from("type,client,tx,amount\ndeposit,1,1,1.0");
 /// let mut rdr = csv::Reader::from_reader(tx.as_bytes());
 /// for result in rdr.records() {
 ///     let tx = Transaction::new_from_csv(result.unwrap());
 /// }
 /// ```
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Transaction {
     #[serde(rename = "type")]
-    pub tx_type: String,
+    pub tx_type: TransactionType,
     pub client: u32,
     pub tx: u32,
-    pub amount: f32,
+    pub amount: Amount,
+    #[serde(rename = "ref", default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    #[serde(default)]
+    pub counterparty: Option<u32>,
+    #[serde(default)]
+    pub currency: Option<String>,
 }
 
 impl Transaction {
     pub fn is_dispute(&self) -> bool {
-        match self.tx_type.as_str() {
-            "dispute" => true,
-            "resolve" => true,
-            "chargeback" => true,
-            _ => false,
-        }
+        matches!(
+            self.tx_type,
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback
+        )
     }
 
     /// Returns the amount to change of this [`Transaction`].
@@ -39,111 +385,555 @@ impl Transaction {
     /// # Examples
     /// It's use for clients implementation [`Client::process_transactions(transactions);`]:
     /// ```
-    /// let change: f32 = transaction.get_amount_change();
-    /// client = client.new_transaction(change);
+    /// # use accounts_cli::money::Amount;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let amount: Amount = "1.5".parse().unwrap();
+    /// let transaction: Transaction = Transaction::new(TransactionType::Withdrawal, 1, 1, amount);
+    /// let change: Amount = transaction.get_amount_change();
+    /// assert_eq!(change, -amount);
     /// ```
-    pub fn get_amount_change(&self) -> f32 {
-        match self.tx_type.as_str() {
-            "deposit" => self.amount,
-            "withdrawal" => self.amount * -1.0,
-            _ => 0.0,
+    pub fn get_amount_change(&self) -> Amount {
+        match self.tx_type {
+            TransactionType::Deposit => self.amount,
+            TransactionType::Withdrawal | TransactionType::Fee => -self.amount,
+            _ => Amount::ZERO,
+        }
+    }
+
+    /// Returns the key that disputes use to find this transaction, or that this dispute uses
+    /// to find the original one, as chosen by `dispute_key`.
+    pub fn dispute_key(&self, key: DisputeKey) -> Option<String> {
+        match key {
+            DisputeKey::Tx => Some(self.tx.to_string()),
+            DisputeKey::Ref => self.reference.clone(),
         }
     }
 
     /// Return the transactions from a csv given the path as parameter.
     /// it's assume that with no further arguments the transactions in the CSV is sorted by the ID
-    /// ```
+    /// ```ignore
     /// transactions.sort_by_key(|a| a.tx);
     /// ```
-    /// # Panics
+    /// Other orders are chosen with the `process_order` of [`Transaction::get_transactions_with`].
     ///
-    /// Panics if the path is invalid.
+    /// # Errors
+    ///
+    /// Returns the [`TransactionError`] of the first record that can't be read, with its line:
+    /// a missing column, a client or tx ID that is not a number, or a bad amount.
     /// # Examples
-    /// ```
+    /// ```no_run
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// # use std::{env, fs};
     /// let args: Vec<String> = env::args().collect();
     /// let path: &String = &args[1];
-    /// let transactions: Vec<Transaction> = Transaction::get_transactions(path);
+    /// let transactions: Vec<Transaction> = Transaction::get_transactions(fs::read_to_string(path)?)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn get_transactions(tx: String) -> Vec<Transaction> {
-        let mut transactions: Vec<Transaction> = Vec::new();
+    pub fn get_transactions(tx: String) -> Result<Vec<Transaction>, TransactionError> {
+        Transaction::get_transactions_with(tx, &Config::default())
+    }
 
+    /// Same as [`Transaction::get_transactions`], but the input is read and checked as told by the [`Config`].
+    /// A fixed-width input is read with [`FixedWidthSpec::records`](super::fixed_width::FixedWidthSpec::records).
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`TransactionError`] of the first record that can't be read or doesn't pass the checks,
+    /// or [`TransactionError::MissingColumn`] if `process_order` is timestamp and there is no `timestamp` column.
+    /// With `skip_bad_rows` those records are reported and skipped instead, see [`Transaction::skip_bad_row`].
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::config::models::Config;
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// # let tx: String = String::from("type,client,tx,amount\ndeposit,1,1,1.0");
+    /// # let args: Vec<String> = vec!["accounts-cli".to_string(), "transactions.csv".to_string()];
+    /// let config: Config = Config::from_args(&args[1..]).unwrap();
+    /// let transactions: Vec<Transaction> = Transaction::get_transactions_with(tx, &config).unwrap();
+    /// ```
+    pub fn get_transactions_with(
+        tx: String,
+        config: &Config,
+    ) -> Result<Vec<Transaction>, TransactionError> {
         /* let mut tx_csv = csv::Reader::from_reader(tx.as_bytes()).flexible_reader(); */
-        let mut tx_csv = csv::ReaderBuilder::new()
-            .flexible(true)
-            .from_reader(tx.as_bytes());
-        for result in tx_csv.records() {
-            let record = Transaction::new_from_csv(result.unwrap());
-            transactions.push(record);
+        let mut refs: DepositRefs = DepositRefs::default();
+        let mut transactions: Vec<Transaction> =
+            Transaction::read_transactions(tx.as_bytes(), &mut refs, config, None)?;
+
+        Transaction::sort_transactions(&mut transactions, config);
+
+        Ok(transactions)
+    }
+
+    /// Read the transactions of an input in file order, checked like [`Transaction::get_transactions_with`].
+    /// The tx IDs and refs are checked against the ones in `refs`, so they don't repeat across several inputs.
+    /// Once the `deadline` passes the rest of the input is not read, for the `timeout` of the [`Config`].
+    pub(crate) fn read_transactions<R: Read>(
+        reader: R,
+        refs: &mut DepositRefs,
+        config: &Config,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Transaction>, TransactionError> {
+        let records: TransactionRecords = TransactionRecords::new(reader, refs, config)?;
+        if config.process_order == ProcessOrder::Timestamp && records.columns().timestamp.is_none()
+        {
+            return Err(TransactionError::MissingColumn {
+                line: 1,
+                column: "timestamp",
+            });
+        }
+
+        //The reading stops at the deadline, the rest of the input is left unread
+        records
+            .skip_bad_rows()
+            .take_while(|_| deadline.is_none_or(|deadline| Instant::now() < deadline))
+            .collect()
+    }
+
+    /// Sort the transactions in the `process_order` of the [`Config`].
+    /// The sorts are stable, the transactions with the same key keep the order of the file.
+    /// With the `timestamp` order the ties are broken by the `timestamp_tiebreak`, file order or tx ID.
+    pub fn sort_transactions(transactions: &mut [Transaction], config: &Config) {
+        match (config.process_order, config.timestamp_tiebreak) {
+            (ProcessOrder::File, _) => {}
+            //This part assume that with no further arguments the transactions in the CSV is sorted by the ID
+            (ProcessOrder::Tx, _) => transactions.sort_by_key(|a| a.tx),
+            (ProcessOrder::Timestamp, TimestampTiebreak::File) => {
+                transactions.sort_by_key(|a| a.timestamp)
+            }
+            (ProcessOrder::Timestamp, TimestampTiebreak::Tx) => {
+                transactions.sort_by_key(|a| (a.timestamp, a.tx))
+            }
+        }
+    }
+
+    /// Merge each run of consecutive deposits of the same client into one, with the tx ID of the first
+    /// and the sum of the amounts, so there are fewer transactions to apply. A deposit referenced by
+    /// a dispute, resolve or chargeback is never merged, it can still be disputed alone.
+    /// A run stops before a sum that would overflow.
+    /// # Examples
+    /// ```
+    /// # use accounts_cli::config::models::Config;
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// let deposit = |tx: u32| Transaction::new(TransactionType::Deposit, 1, tx, "1.0".parse().unwrap());
+    /// let transactions: Vec<Transaction> = vec![deposit(1), deposit(2), deposit(3)];
+    ///
+    /// let merged: Vec<Transaction> = Transaction::coalesce_deposits(transactions, &Config::default());
+    /// assert_eq!(merged.len(), 1);
+    /// assert_eq!(merged[0].amount, "3.0".parse().unwrap());
+    /// ```
+    pub fn coalesce_deposits(transactions: Vec<Transaction>, config: &Config) -> Vec<Transaction> {
+        let disputed: HashSet<String> = transactions
+            .iter()
+            .filter(|t| t.is_dispute())
+            .filter_map(|t| t.dispute_key(config.dispute_key))
+            .collect();
+        let mergeable = |t: &Transaction| -> bool {
+            t.tx_type == TransactionType::Deposit
+                && !t
+                    .dispute_key(config.dispute_key)
+                    .is_some_and(|key| disputed.contains(&key))
+        };
+
+        let mut merged: Vec<Transaction> = Vec::with_capacity(transactions.len());
+        for transaction in transactions {
+            if let Some(last) = merged.last_mut() {
+                if mergeable(last) && mergeable(&transaction) && last.client == transaction.client {
+                    if let Some(sum) = last.amount.checked_add(transaction.amount) {
+                        last.amount = sum;
+                        continue;
+                    }
+                }
+            }
+            merged.push(transaction);
         }
 
-        //This part assume that with no further arguments the transactions in the CSV is sorted by the ID
-        transactions.sort_by_key(|a| a.tx);
+        merged
+    }
+
+    /// Read the transactions of the CSV file one record at a time, in file order, without holding the file
+    /// or the transactions in memory. The records are checked like with [`Transaction::get_transactions`],
+    /// and an item is the error of its record if it can't be read.
+    /// Use [`Client::process_stream`](super::super::clients::models::Client::process_stream) to apply them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`TransactionError`] if the file can't be opened or its header can't be read.
+    /// # Examples
+    /// ```no_run
+    /// # use accounts_cli::clients::models::Client;
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// let clients: Vec<Client> = Client::process_stream(Transaction::stream_from_path("transactions.csv")?)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stream_from_path(
+        path: &str,
+    ) -> Result<impl Iterator<Item = Result<Transaction, TransactionError>>, TransactionError> {
+        let config: Config = Config::default();
+        let mut tx_csv = config.csv_reader().from_path(path)?;
+        let columns: Columns = Columns::read_headers(tx_csv.headers()?, &config)?;
+        let mut refs: DepositRefs = DepositRefs::default();
+
+        //A repeated tx ID is dropped like in the other inputs, see Transaction::skip_bad_row
+        Ok(tx_csv.into_records().filter_map(move |record| {
+            match record
+                .map_err(TransactionError::from)
+                .and_then(|sr| refs.read(&sr, &columns, &config))
+            {
+                Ok(transaction) => Some(Ok(transaction)),
+                Err(e) => Transaction::skip_bad_row(e, &config).err().map(Err),
+            }
+        }))
+    }
+
+    /// Decide what to do with a record that can't be read: with `skip_bad_rows` the error is
+    /// printed to stderr and the record skipped, otherwise the error is returned to stop the run.
+    /// A repeated tx ID is always skipped this way, so the first row wins, unless `strict_tx_ids` is set.
+    /// An input that can't be read, like a corrupt gzip file, is not a bad row and always stops the run.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error back unless `skip_bad_rows` is set, and always for a [`TransactionError::Read`].
+    pub fn skip_bad_row(e: TransactionError, config: &Config) -> Result<(), TransactionError> {
+        let repeated_tx: bool =
+            matches!(e, TransactionError::DuplicateTx { .. }) && !config.strict_tx_ids;
+        if matches!(e, TransactionError::Read { .. }) || (!config.skip_bad_rows && !repeated_tx) {
+            return Err(e);
+        }
 
-        return transactions;
+        log::warn!("Skipping {}", e);
+        Ok(())
     }
 
     /// Get the transaction index from a vec of transactions.
     /// Search one with the same ID and deposit like transaction type.
+    /// It scans the whole vec for every call, the disputes of a run are matched by the
+    /// [`ClientLedger`](crate::clients::ledger::ClientLedger), which keeps the deposits by ID.
     ///
     /// # Examples
     /// ```
-    /// Transaction::get_prev_trans(txs, transaction.tx)
+    /// # use accounts_cli::transactions::models::{Transaction, TransactionType};
+    /// # use accounts_cli::money::Amount;
+    /// # let txs: Vec<Transaction> = vec![Transaction::new(TransactionType::Deposit, 1, 1, "1.0".parse().unwrap())];
+    /// # let transaction: Transaction = Transaction::new(TransactionType::Dispute, 1, 1, Amount::ZERO);
+    /// Transaction::get_prev_trans(&txs, transaction.tx)
+    /// # ;
     /// ```
-    pub fn get_prev_trans(txs: &Vec<Transaction>, tx_id: u32) -> Option<usize> {
+    #[deprecated(note = "the deposits are looked up by the ClientLedger, in constant time")]
+    pub fn get_prev_trans(txs: &[Transaction], tx_id: u32) -> Option<usize> {
         txs.iter()
-            .position(|tx| tx.tx == tx_id && tx.tx_type.as_str() == "deposit")
+            .position(|tx| tx.tx == tx_id && tx.tx_type == TransactionType::Deposit)
     }
 
-    pub fn new(tx_type: String, client: u32, tx: u32, amount: f32) -> Self {
+    pub fn new(tx_type: TransactionType, client: u32, tx: u32, amount: Amount) -> Self {
         Self {
             tx_type,
             client,
             tx,
             amount,
+            reference: None,
+            timestamp: None,
+            counterparty: None,
+            currency: None,
         }
     }
 
+    /// Generate a new transaction from a [`csv::StringRecord`] after checking it against the [`Config`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TransactionError`] with the line of the record if a column is missing or can't be parsed,
+    /// or if the amount is more precise than `max_input_decimals`.
+    pub fn new_from_record(
+        sr: &csv::StringRecord,
+        columns: &Columns,
+        config: &Config,
+    ) -> Result<Self, TransactionError> {
+        let line: u64 = sr.position().map_or(0, |p| p.line());
+        let field = |index: usize, column: &'static str| {
+            sr.get(index)
+                .map(|f| f.trim())
+                .ok_or(TransactionError::MissingColumn { line, column })
+        };
+
+        let tx_type: &str = sr
+            .get(columns.tx_type)
+            .ok_or(TransactionError::MissingColumn {
+                line,
+                column: "type",
+            })?;
+        //A synonym is replaced by its type, so the ledger only sees the canonical ones
+        let tx_type: &str = tx_type.trim();
+        let tx_type: TransactionType = TransactionType::parse(
+            config
+                .type_aliases
+                .get(tx_type)
+                .map_or(tx_type, |canonical| canonical.as_str()),
+        )
+        .map_err(|_| TransactionError::InvalidType {
+            line,
+            value: tx_type.to_string(),
+        })?;
+        let client: &str = field(columns.client, "client")?;
+        let client: u32 = client
+            .parse::<u32>()
+            .map_err(|_| TransactionError::InvalidClient {
+                line,
+                value: client.to_string(),
+            })?;
+        //The IDs are read as u32, a smaller width like u16 is checked apart
+        let check_client = |client: u32| -> Result<u32, TransactionError> {
+            match config.max_client_id {
+                Some(max) if client > max => {
+                    Err(TransactionError::ClientIdTooLarge { line, client, max })
+                }
+                _ => Ok(client),
+            }
+        };
+        let client: u32 = check_client(client)?;
+        let tx: &str = field(columns.tx, "tx")?;
+        let tx: u32 = tx.parse::<u32>().map_err(|_| TransactionError::InvalidTx {
+            line,
+            value: tx.to_string(),
+        })?;
+        //A missing or empty amount, like the trailing comma of `dispute,1,1,`, is zero
+        let amount: Amount = match sr
+            .get(columns.amount)
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+        {
+            Some(a) => {
+                //A number with the other separator would be misread, like 1,5 without --decimal-comma
+                let (separator, other): (char, char) = match config.decimal_comma {
+                    true => (',', '.'),
+                    false => ('.', ','),
+                };
+                if a.contains(other) {
+                    return Err(TransactionError::DecimalSeparator {
+                        line,
+                        amount: a.to_string(),
+                        expected: separator,
+                    });
+                }
+
+                let decimals: usize = match a.split_once(separator) {
+                    Some((_, decimals)) => decimals.len(),
+                    None => 0,
+                };
+
+                match config.max_input_decimals {
+                    Some(max) if decimals > max => {
+                        return Err(TransactionError::TooManyDecimals {
+                            line,
+                            amount: a.to_string(),
+                            max,
+                        });
+                    }
+                    _ => {}
+                }
+
+                let amount: Amount = a.replace(separator, ".").parse::<Amount>().map_err(|_| {
+                    TransactionError::InvalidAmount {
+                        line,
+                        value: a.to_string(),
+                    }
+                })?;
+
+                //The scale is applied to the amount as read, an overflow is an invalid amount
+                match config.scale {
+                    Some(factor) => {
+                        amount
+                            .checked_mul(factor)
+                            .ok_or(TransactionError::InvalidAmount {
+                                line,
+                                value: a.to_string(),
+                            })?
+                    }
+                    None => amount,
+                }
+            }
+            None => Amount::ZERO,
+        };
+        let magnitude: Amount = if amount.is_negative() {
+            -amount
+        } else {
+            amount
+        };
+        if let Some(max) = config.max_amount.filter(|max| magnitude > *max) {
+            return Err(TransactionError::AmountTooLarge { line, tx, max });
+        }
+        //The amount of a dispute, resolve or chargeback is optional, the other types move it
+        if amount.is_negative()
+            && matches!(
+                tx_type,
+                TransactionType::Deposit
+                    | TransactionType::Withdrawal
+                    | TransactionType::Transfer
+                    | TransactionType::Fee
+            )
+        {
+            return Err(TransactionError::NegativeAmount { line, tx });
+        }
+        let reference: Option<String> = columns
+            .reference
+            .and_then(|index| sr.get(index))
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+            .map(|r| r.to_string());
+        let timestamp: Option<u64> = match columns
+            .timestamp
+            .and_then(|index| sr.get(index))
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+        {
+            Some(t) => Some(
+                t.parse::<u64>()
+                    .map_err(|_| TransactionError::InvalidTimestamp {
+                        line,
+                        value: t.to_string(),
+                    })?,
+            ),
+            None if config.process_order == ProcessOrder::Timestamp => {
+                return Err(TransactionError::MissingColumn {
+                    line,
+                    column: "timestamp",
+                });
+            }
+            None => None,
+        };
+        let counterparty: Option<u32> = match columns
+            .counterparty
+            .and_then(|index| sr.get(index))
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+        {
+            Some(c) => Some(check_client(c.parse::<u32>().map_err(|_| {
+                TransactionError::InvalidClient {
+                    line,
+                    value: c.to_string(),
+                }
+            })?)?),
+            None if tx_type == TransactionType::Transfer => {
+                return Err(TransactionError::MissingColumn {
+                    line,
+                    column: "counterparty",
+                });
+            }
+            None => None,
+        };
+        let currency: Option<String> = columns
+            .currency
+            .and_then(|index| sr.get(index))
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .map(|c| c.to_uppercase());
+
+        Ok(Self {
+            tx_type,
+            client,
+            tx,
+            amount,
+            reference,
+            timestamp,
+            counterparty,
+            currency,
+        })
+    }
+
     /// Generate a new transaction from a [`csv::StringRecord`]
     ///
     /// # Example
     /// You can create a transaction from  [`csv::StringRecord`] with [`Transaction::new_from_csv`]:
     /// ```
+    /// # use accounts_cli::transactions::models::Transaction;
+    /// # let tx: String = String::from("type,client,tx,amount\ndeposit,1,1,1.0");
     /// let mut rdr = csv::Reader::from_reader(tx.as_bytes());
     /// for result in rdr.records() {
     ///     let tx = Transaction::new_from_csv(result.unwrap());
     /// }
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a column is missing or can't be parsed, [`Transaction::new_from_record`] returns the error instead.
     pub fn new_from_csv(sr: csv::StringRecord) -> Self {
-        let tx_type: String = sr.get(0).unwrap().to_string();
-        let client: u32 = sr.get(1).unwrap().trim().parse::<u32>().unwrap();
-        let tx: u32 = sr.get(2).unwrap().trim().parse::<u32>().unwrap();
-        let amount: f32 = match sr.get(3) {
-            Some(a) => a.trim().parse::<f32>().unwrap(),
-            None => 0.0,
-        };
+        match Transaction::new_from_record(&sr, &Columns::default(), &Config::default()) {
+            Ok(transaction) => transaction,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
 
-        Self {
-            tx_type,
-            client,
-            tx,
-            amount,
+/// The refs of the deposits read so far, to check that `--dispute-key ref` finds a single deposit,
+/// and the tx IDs of the deposits, withdrawals and transfers, to check they don't repeat.
+#[derive(Debug, Default)]
+pub struct DepositRefs {
+    seen: HashSet<String>,
+    tx_ids: HashSet<u32>,
+}
+
+impl DepositRefs {
+    /// Read the transaction of the record with [`Transaction::new_from_record`] and,
+    /// if disputes find deposits by ref, check that no other deposit has the same one.
+    /// Disputes, resolves and chargebacks can share the tx ID of the transaction they reference,
+    /// any other transaction must have its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`Transaction::new_from_record`], [`TransactionError::DuplicateRef`]
+    /// or [`TransactionError::DuplicateTx`].
+    pub fn read(
+        &mut self,
+        sr: &csv::StringRecord,
+        columns: &Columns,
+        config: &Config,
+    ) -> Result<Transaction, TransactionError> {
+        let transaction: Transaction = Transaction::new_from_record(sr, columns, config)?;
+
+        if !transaction.is_dispute() && !self.tx_ids.insert(transaction.tx) {
+            return Err(TransactionError::DuplicateTx {
+                line: sr.position().map_or(0, |p| p.line()),
+                tx: transaction.tx,
+            });
+        }
+
+        if config.dispute_key == DisputeKey::Ref && transaction.tx_type == TransactionType::Deposit
+        {
+            if let Some(reference) = &transaction.reference {
+                if !self.seen.insert(reference.clone()) {
+                    return Err(TransactionError::DuplicateRef {
+                        line: sr.position().map_or(0, |p| p.line()),
+                        reference: reference.clone(),
+                    });
+                }
+            }
         }
+
+        Ok(transaction)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::super::clients::models::Client;
     use super::*;
 
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
     #[test]
     fn new_transaction_test() {
         let sr: csv::StringRecord = csv::StringRecord::from(vec!["deposit", "1", "1", "1.0"]);
         let tx_csv: Transaction = Transaction::new_from_csv(sr);
         let tx: Transaction = Transaction {
-            tx_type: "deposit".to_string(),
+            tx_type: TransactionType::Deposit,
             client: 1,
             tx: 1,
-            amount: 1.0,
+            amount: amount("1.0"),
+            reference: None,
+            timestamp: None,
+            counterparty: None,
+            currency: None,
         };
         assert_eq!(tx, tx_csv);
     }
@@ -151,28 +941,661 @@ mod tests {
     #[test]
     fn amount_test() {
         let tx: Transaction = Transaction {
-            tx_type: "deposit".to_string(),
+            tx_type: TransactionType::Deposit,
             client: 1,
             tx: 1,
-            amount: 1.0,
+            amount: amount("1.0"),
+            reference: None,
+            timestamp: None,
+            counterparty: None,
+            currency: None,
         };
 
-        assert_eq!(tx.get_amount_change(), 1.0)
+        assert_eq!(tx.get_amount_change(), amount("1.0"))
+    }
+
+    #[test]
+    fn empty_amount_test() {
+        let tx_string: String = String::from(
+            "type, client, tx, amount\n\
+             deposit, 1, 1, 2.0\n\
+             dispute, 1, 1\n\
+             resolve, 1, 1,\n\
+             dispute, 1, 1,  ",
+        );
+        let txs: Vec<Transaction> = Transaction::get_transactions(tx_string).unwrap();
+
+        assert_eq!(txs.len(), 4);
+        assert!(txs[1..].iter().all(|tx| tx.amount == Amount::ZERO));
     }
 
     #[test]
     fn get_transactions_test() {
         let tx_string: String = String::from("type, client, tx, amount\ndeposit, 1, 1, 1.0");
-        let tx_csv: Vec<Transaction> = Transaction::get_transactions(tx_string);
+        let tx_csv: Vec<Transaction> = Transaction::get_transactions(tx_string).unwrap();
 
         let tx: Transaction = Transaction {
-            tx_type: "deposit".to_string(),
+            tx_type: TransactionType::Deposit,
             client: 1,
             tx: 1,
-            amount: 1.0,
+            amount: amount("1.0"),
+            reference: None,
+            timestamp: None,
+            counterparty: None,
+            currency: None,
         };
         let txs: Vec<Transaction> = vec![tx];
 
         assert_eq!(txs, tx_csv);
     }
+
+    #[test]
+    fn new_from_record_error_test() {
+        let config: Config = Config::default();
+        let columns: Columns = Columns::default();
+
+        let sr: csv::StringRecord = csv::StringRecord::from(vec!["deposit", "one", "1", "1.0"]);
+        assert_eq!(
+            Transaction::new_from_record(&sr, &columns, &config),
+            Err(TransactionError::InvalidClient {
+                line: 0,
+                value: "one".to_string(),
+            })
+        );
+
+        let sr: csv::StringRecord = csv::StringRecord::from(vec!["deposit", "1"]);
+        assert_eq!(
+            Transaction::new_from_record(&sr, &columns, &config),
+            Err(TransactionError::MissingColumn {
+                line: 0,
+                column: "tx",
+            })
+        );
+
+        let sr: csv::StringRecord = csv::StringRecord::from(vec!["deposit", "1", "1", "a lot"]);
+        assert!(matches!(
+            Transaction::new_from_record(&sr, &columns, &config),
+            Err(TransactionError::InvalidAmount { .. })
+        ));
+    }
+
+    #[test]
+    fn skip_bad_rows_test() {
+        let tx_string: String = String::from(
+            "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, x, 2, 1.0\ndeposit, 1, 3, 2.0",
+        );
+
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string.clone(), &Config::default()),
+            Err(TransactionError::InvalidClient {
+                line: 3,
+                value: "x".to_string(),
+            })
+        );
+
+        let config: Config = Config {
+            skip_bad_rows: true,
+            ..Config::default()
+        };
+        let txs: Vec<Transaction> = Transaction::get_transactions_with(tx_string, &config).unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[1].tx, 3);
+    }
+
+    #[test]
+    fn dispute_ref_test() {
+        let config: Config = Config {
+            dispute_key: DisputeKey::Ref,
+            ..Config::default()
+        };
+
+        let tx_string: String = String::from(
+            "type, client, tx, amount, ref\ndeposit, 1, 1, 10.0, A-1\ndeposit, 1, 2, 5.0\ndispute, 1, 3, 0.0, A-1",
+        );
+        let txs: Vec<Transaction> = Transaction::get_transactions_with(tx_string, &config).unwrap();
+        assert_eq!(txs[0].reference, Some("A-1".to_string()));
+        assert_eq!(txs[1].reference, None);
+        assert_eq!(txs[2].dispute_key(DisputeKey::Ref), Some("A-1".to_string()));
+        assert_eq!(txs[2].dispute_key(DisputeKey::Tx), Some("3".to_string()));
+
+        let tx_string: String = String::from(
+            "type, client, tx, amount, ref\ndeposit, 1, 1, 10.0, A-1\ndeposit, 1, 2, 5.0, A-1",
+        );
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::DuplicateRef {
+                line: 3,
+                reference: "A-1".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn max_input_decimals_test() {
+        let config: Config = Config {
+            max_input_decimals: Some(4),
+            ..Config::default()
+        };
+
+        let tx_string: String = String::from("type, client, tx, amount\ndeposit, 1, 1, 1.2345");
+        assert!(Transaction::get_transactions_with(tx_string, &config).is_ok());
+
+        let tx_string: String =
+            String::from("type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, 1, 2, 1.23456");
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::TooManyDecimals {
+                line: 3,
+                amount: "1.23456".to_string(),
+                max: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn header_test() {
+        let headers: csv::StringRecord =
+            csv::StringRecord::from(vec!["type", " client", " tx", " amount", " ref"]);
+        assert!(Columns::check_headers(&headers).is_empty());
+
+        let headers: csv::StringRecord =
+            csv::StringRecord::from(vec!["type", "client", "id", "amount", "memo"]);
+        assert_eq!(
+            Columns::check_headers(&headers),
+            vec![
+                "missing column \"tx\"".to_string(),
+                "extra column \"id\"".to_string(),
+                "extra column \"memo\"".to_string(),
+            ]
+        );
+
+        //A missing column stops the run even without --strict-header
+        assert_eq!(
+            Transaction::get_transactions_with(
+                String::from("type, client, id, amount\ndeposit, 1, 1, 1.0"),
+                &Config::default()
+            ),
+            Err(TransactionError::InvalidHeader {
+                line: 1,
+                message: "missing column \"tx\", extra column \"id\"".to_string(),
+            })
+        );
+
+        //The columns are found by name in any order
+        let txs: Vec<Transaction> = Transaction::get_transactions_with(
+            String::from("amount, tx, type, client\n1.5, 7, deposit, 2"),
+            &Config::default(),
+        )
+        .unwrap();
+        assert_eq!(txs[0].tx_type, TransactionType::Deposit);
+        assert_eq!((txs[0].client, txs[0].tx), (2, 7));
+        assert_eq!(txs[0].amount, amount("1.5"));
+
+        //The extra column is only warned
+        let tx_string: String =
+            String::from("type, client, tx, amount, memo\ndeposit, 1, 1, 1.0, rent");
+        let txs: Vec<Transaction> =
+            Transaction::get_transactions_with(tx_string.clone(), &Config::default()).unwrap();
+        assert_eq!(txs[0].amount, amount("1.0"));
+
+        let config: Config = Config {
+            strict_header: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::InvalidHeader {
+                line: 1,
+                message: "extra column \"memo\"".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn process_order_test() {
+        let tx_string: String = String::from(
+            "type, client, tx, amount, timestamp\n\
+             deposit, 1, 3, 1.0, 20\n\
+             deposit, 1, 1, 1.0, 30\n\
+             deposit, 1, 2, 1.0, 10",
+        );
+        let order = |process_order: ProcessOrder| -> Vec<u32> {
+            let config: Config = Config {
+                process_order,
+                ..Config::default()
+            };
+            Transaction::get_transactions_with(tx_string.clone(), &config)
+                .unwrap()
+                .iter()
+                .map(|t| t.tx)
+                .collect()
+        };
+
+        assert_eq!(order(ProcessOrder::File), vec![3, 1, 2]);
+        assert_eq!(order(ProcessOrder::Tx), vec![1, 2, 3]);
+        assert_eq!(order(ProcessOrder::Timestamp), vec![2, 3, 1]);
+
+        let config: Config = Config {
+            process_order: ProcessOrder::Timestamp,
+            ..Config::default()
+        };
+        let tx_string: String = String::from("type, client, tx, amount\ndeposit, 1, 1, 1.0");
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::MissingColumn {
+                line: 1,
+                column: "timestamp",
+            })
+        );
+
+        let tx_string: String =
+            String::from("type, client, tx, amount, timestamp\ndeposit, 1, 1, 1.0, noon");
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::InvalidTimestamp {
+                line: 2,
+                value: "noon".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn timestamp_tiebreak_test() {
+        let tx_string: String = String::from(
+            "type, client, tx, amount, timestamp\n\
+             deposit, 1, 1, 5.0, 10\n\
+             withdrawal, 1, 3, 5.0, 20\n\
+             deposit, 1, 2, 1.0, 20",
+        );
+        let order = |timestamp_tiebreak: TimestampTiebreak| -> Vec<u32> {
+            let config: Config = Config {
+                process_order: ProcessOrder::Timestamp,
+                timestamp_tiebreak,
+                ..Config::default()
+            };
+            Transaction::get_transactions_with(tx_string.clone(), &config)
+                .unwrap()
+                .iter()
+                .map(|t| t.tx)
+                .collect()
+        };
+
+        //The same every time, only the ones with the same timestamp are reordered
+        for _ in 0..3 {
+            assert_eq!(order(TimestampTiebreak::File), vec![1, 3, 2]);
+            assert_eq!(order(TimestampTiebreak::Tx), vec![1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn coalesce_deposits_test() {
+        let tx = |tx_type: &str, client: u32, tx: u32, value: &str| -> Transaction {
+            Transaction::new(
+                TransactionType::parse(tx_type).unwrap(),
+                client,
+                tx,
+                amount(value),
+            )
+        };
+        let transactions: Vec<Transaction> = vec![
+            tx("deposit", 1, 1, "1.0"),
+            tx("deposit", 1, 2, "2.0"),
+            tx("deposit", 2, 3, "1.0"),
+            tx("deposit", 1, 4, "1.0"),
+            tx("deposit", 1, 5, "3.0"),
+            tx("deposit", 1, 6, "1.0"),
+            tx("withdrawal", 1, 7, "1.0"),
+            tx("deposit", 1, 8, "1.0"),
+            tx("dispute", 1, 5, "0.0"),
+        ];
+
+        //Only consecutive deposits of a client are merged, never the disputed one
+        let merged: Vec<(u32, Amount)> =
+            Transaction::coalesce_deposits(transactions, &Config::default())
+                .iter()
+                .map(|t| (t.tx, t.amount))
+                .collect();
+        assert_eq!(
+            merged,
+            [
+                (1, amount("3.0")),
+                (3, amount("1.0")),
+                (4, amount("1.0")),
+                (5, amount("3.0")),
+                (6, amount("1.0")),
+                (7, amount("1.0")),
+                (8, amount("1.0")),
+                (5, amount("0.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_alias_test() {
+        let config: Config = Config::from_args(&[
+            "--type-alias".to_string(),
+            "credit=deposit,debit=withdrawal".to_string(),
+        ])
+        .unwrap();
+        let tx_string: String = String::from(
+            "type, client, tx, amount\ncredit, 1, 1, 5.0\ndebit, 1, 2, 2.0\ndeposit, 1, 3, 1.0",
+        );
+        let txs: Vec<Transaction> =
+            Transaction::get_transactions_with(tx_string.clone(), &config).unwrap();
+
+        let types: Vec<&str> = txs.iter().map(|tx| tx.tx_type.as_str()).collect();
+        assert_eq!(types, ["deposit", "withdrawal", "deposit"]);
+
+        let client: Client = Client::process_transactions(&txs)[0];
+        assert_eq!(client.available(), amount("4.0"));
+
+        //A type that is neither known nor an alias is a bad row
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string + "\nrefund, 1, 4, 1.0", &config),
+            Err(TransactionError::InvalidType {
+                line: 5,
+                value: "refund".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn delimiter_test() {
+        for (delimiter, tx_string) in [
+            (
+                b'\t',
+                "type\tclient\ttx\tamount\ndeposit\t1\t1\t2.0\nwithdrawal\t1\t2\t0.5\n",
+            ),
+            (
+                b';',
+                "type; client; tx; amount\ndeposit; 1; 1; 2.0\nwithdrawal ;1 ; 2;0.5\n",
+            ),
+        ] {
+            let config: Config = Config {
+                delimiter: Some(delimiter),
+                ..Config::default()
+            };
+            let txs: Vec<Transaction> =
+                Transaction::get_transactions_with(tx_string.to_string(), &config).unwrap();
+            let rows: Vec<(TransactionType, u32, u32, Amount)> = txs
+                .iter()
+                .map(|t| (t.tx_type, t.client, t.tx, t.amount))
+                .collect();
+            assert_eq!(
+                rows,
+                [
+                    (TransactionType::Deposit, 1, 1, amount("2.0")),
+                    (TransactionType::Withdrawal, 1, 2, amount("0.5"))
+                ]
+            );
+        }
+
+        //The fields of a semicolon file are one column without the option
+        let tx_string: String = String::from("type;client;tx;amount\ndeposit;1;1;2.0\n");
+        assert!(Transaction::get_transactions(tx_string).is_err());
+    }
+
+    #[test]
+    fn negative_amount_test() {
+        for tx_type in ["deposit", "withdrawal"] {
+            let tx_string: String = format!("type, client, tx, amount\n{}, 1, 7, -50.0", tx_type);
+            assert_eq!(
+                Transaction::get_transactions(tx_string),
+                Err(TransactionError::NegativeAmount { line: 2, tx: 7 })
+            );
+        }
+
+        //A zero deposit and a dispute without amount are fine
+        let tx_string: String =
+            String::from("type, client, tx, amount\ndeposit, 1, 1, 0.0\ndispute, 1, 1");
+        assert_eq!(Transaction::get_transactions(tx_string).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn max_client_id_test() {
+        let config: Config = Config {
+            max_client_id: Some(u16::MAX as u32),
+            ..Config::default()
+        };
+        let read = |client: &str, counterparty: &str| {
+            Transaction::get_transactions_with(
+                format!(
+                    "type, client, tx, amount, counterparty\ntransfer, {}, 1, 1.0, {}",
+                    client, counterparty
+                ),
+                &config,
+            )
+        };
+
+        let txs: Vec<Transaction> = read("65535", "1").unwrap();
+        assert_eq!(txs[0].client, 65535);
+        assert_eq!(
+            read("65536", "1"),
+            Err(TransactionError::ClientIdTooLarge {
+                line: 2,
+                client: 65536,
+                max: 65535,
+            })
+        );
+        assert!(matches!(
+            read("1", "70000"),
+            Err(TransactionError::ClientIdTooLarge { client: 70000, .. })
+        ));
+
+        //Without the option any u32 is a client ID
+        let tx_string: String =
+            String::from("type, client, tx, amount\ndeposit, 4294967295, 1, 1.0");
+        assert_eq!(
+            Transaction::get_transactions(tx_string).unwrap()[0].client,
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn amount_overflow_test() {
+        //Amounts are fixed-point, there is no infinity nor NaN to parse into
+        for value in ["inf", "-inf", "nan", "NaN", "1e40", "99999999999999999999"] {
+            let tx_string: String = format!("type, client, tx, amount\ndeposit, 1, 7, {}", value);
+            assert_eq!(
+                Transaction::get_transactions(tx_string),
+                Err(TransactionError::InvalidAmount {
+                    line: 2,
+                    value: value.to_string(),
+                })
+            );
+        }
+
+        //The largest amount that fits is read exactly, unless it's above --max-amount
+        let tx_string: String =
+            String::from("type, client, tx, amount\ndeposit, 1, 7, 99999999999999.9999");
+        assert_eq!(
+            Transaction::get_transactions(tx_string.clone()).unwrap()[0].amount,
+            Amount::from_scaled(999_999_999_999_999_999)
+        );
+        let config: Config = Config {
+            max_amount: Some(amount("1000000")),
+            ..Config::default()
+        };
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::AmountTooLarge {
+                line: 2,
+                tx: 7,
+                max: amount("1000000"),
+            })
+        );
+        let tx_string: String = String::from(
+            "type, client, tx, amount\ndeposit, 1, 1, 1000000\ndispute, 1, 1, -1000000.0001",
+        );
+        assert!(matches!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::AmountTooLarge { line: 3, tx: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn duplicate_tx_test() {
+        let tx_string: String = String::from(
+            "type, client, tx, amount\ndeposit, 1, 1, 5.0\ndispute, 1, 1\ndeposit, 2, 1, 7.0\nwithdrawal, 1, 2, 1.0",
+        );
+
+        //The first row with the tx ID wins, the dispute can share it
+        let txs: Vec<Transaction> = Transaction::get_transactions(tx_string.clone()).unwrap();
+        let rows: Vec<(u32, u32, Amount)> =
+            txs.iter().map(|t| (t.client, t.tx, t.amount)).collect();
+        assert_eq!(
+            rows,
+            [
+                (1, 1, amount("5.0")),
+                (1, 1, amount("0.0")),
+                (1, 2, amount("1.0"))
+            ]
+        );
+
+        let config: Config = Config {
+            strict_tx_ids: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::DuplicateTx { line: 4, tx: 1 })
+        );
+    }
+
+    #[test]
+    fn transaction_type_test() {
+        for tx_type in TRANSACTION_TYPES {
+            assert_eq!(TransactionType::parse(tx_type).unwrap().as_str(), tx_type);
+        }
+
+        //A type with the wrong case is rejected, not applied nor skipped silently
+        let tx_string: String =
+            String::from("type, client, tx, amount\ndeposit, 1, 1, 5.0\nDeposit, 1, 2, 2.0");
+        assert_eq!(
+            Transaction::get_transactions(tx_string),
+            Err(TransactionError::InvalidType {
+                line: 3,
+                value: "Deposit".to_string()
+            })
+        );
+
+        let sr: csv::StringRecord = csv::StringRecord::from(vec!["withdraw", "1", "1", "1.0"]);
+        assert!(
+            Transaction::new_from_record(&sr, &Columns::default(), &Config::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn decimal_comma_test() {
+        let config: Config = Config {
+            decimal_comma: true,
+            ..Config::default()
+        };
+        let tx_string: String =
+            String::from("type; client; tx; amount\ndeposit; 1; 1; 1,5\ndeposit; 1; 2; 2\n");
+        let mut tx_csv = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .from_reader(tx_string.as_bytes());
+        let records: Vec<csv::StringRecord> = tx_csv.records().map(|r| r.unwrap()).collect();
+
+        let columns: Columns = Columns::default();
+        let tx: Transaction = Transaction::new_from_record(&records[0], &columns, &config).unwrap();
+        assert_eq!(tx.amount, amount("1.5"));
+        let tx: Transaction = Transaction::new_from_record(&records[1], &columns, &config).unwrap();
+        assert_eq!(tx.amount, amount("2"));
+
+        //A dot amount under --decimal-comma
+        let tx_string: String = String::from("type, client, tx, amount\ndeposit, 1, 1, 1.5");
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::DecimalSeparator {
+                line: 2,
+                amount: "1.5".to_string(),
+                expected: ',',
+            })
+        );
+
+        //A comma amount without it
+        let tx_string: String = String::from("type,client,tx,amount\ndeposit,1,1,\"1,5\"");
+        let error: TransactionError =
+            Transaction::get_transactions_with(tx_string, &Config::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2: amount \"1,5\" uses the decimal separator ',', expected '.'"
+        );
+    }
+
+    #[test]
+    fn scale_test() {
+        let config: Config = Config {
+            scale: Some(amount("0.001")),
+            ..Config::default()
+        };
+        let tx_string: String = String::from(
+            "type, client, tx, amount\ndeposit, 1, 1, 1234.5\ndeposit, 1, 2, 0.05\nwithdrawal, 1, 3, 1000",
+        );
+        let txs: Vec<Transaction> = Transaction::get_transactions_with(tx_string, &config).unwrap();
+        let amounts: Vec<Amount> = txs.iter().map(|tx| tx.amount).collect();
+        assert_eq!(amounts, [amount("1.2345"), amount("0.0001"), amount("1")]);
+
+        let config: Config = Config {
+            scale: Some(amount("1000000000")),
+            ..Config::default()
+        };
+        let tx_string: String = String::from("type, client, tx, amount\ndeposit, 1, 1, 100000000");
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &config),
+            Err(TransactionError::InvalidAmount {
+                line: 2,
+                value: "100000000".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn get_transactions_error_test() {
+        let tx_string: String =
+            String::from("type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, alice, 2, 1.0");
+        let error: TransactionError = Transaction::get_transactions(tx_string).unwrap_err();
+
+        assert_eq!(
+            error,
+            TransactionError::InvalidClient {
+                line: 3,
+                value: "alice".to_string(),
+            }
+        );
+        assert_eq!(error.to_string(), "Line 3: invalid client ID \"alice\"");
+    }
+
+    #[test]
+    fn currency_test() {
+        let txs: Vec<Transaction> = Transaction::get_transactions(
+            "type, client, tx, amount, currency\ndeposit, 1, 1, 2.0, jpy\ndeposit, 2, 2, 1.0,"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(txs[0].currency, Some("JPY".to_string()));
+        assert_eq!(txs[1].currency, None);
+    }
+
+    #[test]
+    fn counterparty_test() {
+        let tx_string: String = String::from(
+            "type, client, tx, amount, counterparty\ntransfer, 1, 1, 2.0, 2\ndeposit, 1, 2, 1.0,",
+        );
+        let txs: Vec<Transaction> =
+            Transaction::get_transactions_with(tx_string, &Config::default()).unwrap();
+        assert_eq!(txs[0].counterparty, Some(2));
+        assert_eq!(txs[1].counterparty, None);
+
+        let tx_string: String = String::from("type, client, tx, amount\ntransfer, 1, 1, 2.0");
+        assert_eq!(
+            Transaction::get_transactions_with(tx_string, &Config::default()),
+            Err(TransactionError::MissingColumn {
+                line: 2,
+                column: "counterparty",
+            })
+        );
+    }
 }
@@ -0,0 +1,177 @@
+use super::super::config::models::Config;
+use super::models::{Columns, DepositRefs, Transaction, TransactionError};
+use std::io::{BufRead, BufReader, Read};
+
+/// The transactions of an input, read one record at a time as told by the [`Config`].
+/// Each item is the transaction of a record or the error of the record, with its line.
+/// The strict and lenient callers share it: [`TransactionRecords::skip_bad_rows`] skips the bad rows with `skip_bad_rows`.
+/// # Examples
+/// ```
+/// # use accounts_cli::config::models::Config;
+/// # use accounts_cli::transactions::models::DepositRefs;
+/// # use accounts_cli::transactions::records::TransactionRecords;
+/// let tx: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, one, 2, 1.0\n";
+/// let config: Config = Config::default();
+/// let mut refs: DepositRefs = DepositRefs::default();
+///
+/// let results: Vec<_> = TransactionRecords::new(tx.as_bytes(), &mut refs, &config)
+///     .unwrap()
+///     .collect();
+/// assert!(results[0].is_ok());
+/// assert!(results[1].is_err());
+/// ```
+pub struct TransactionRecords<'a> {
+    records: Box<dyn Iterator<Item = Result<csv::StringRecord, TransactionError>> + 'a>,
+    columns: Columns,
+    refs: &'a mut DepositRefs,
+    config: &'a Config,
+}
+
+impl<'a> TransactionRecords<'a> {
+    /// Start reading the input, a CSV with header or fixed-width records if the [`Config`] has a spec.
+    /// The refs are shared with other inputs so that a ref can't repeat across them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`TransactionError`] of the header, see [`Columns::read_headers`].
+    pub fn new<R: Read + 'a>(
+        reader: R,
+        refs: &'a mut DepositRefs,
+        config: &'a Config,
+    ) -> Result<Self, TransactionError> {
+        let (columns, records): (
+            Columns,
+            Box<dyn Iterator<Item = Result<csv::StringRecord, TransactionError>> + 'a>,
+        ) = match &config.fixed_width {
+            Some(spec) => (
+                Columns::default(),
+                Box::new(BufReader::new(reader).lines().enumerate().filter_map(
+                    move |(index, line)| {
+                        let line_number: u64 = index as u64 + 1;
+                        match line {
+                            Ok(line) => spec.record(&line, line_number).map(Ok),
+                            Err(e) => Some(Err(TransactionError::Read {
+                                line: line_number,
+                                message: e.to_string(),
+                            })),
+                        }
+                    },
+                )),
+            ),
+            None => {
+                let mut tx_csv = config.csv_reader().from_reader(reader);
+                let columns: Columns = Columns::read_headers(tx_csv.headers()?, config)?;
+                (
+                    columns,
+                    Box::new(tx_csv.into_records().map(|result| Ok(result?))),
+                )
+            }
+        };
+
+        Ok(Self {
+            records,
+            columns,
+            refs,
+            config,
+        })
+    }
+
+    /// Returns the columns found in the header.
+    pub fn columns(&self) -> &Columns {
+        &self.columns
+    }
+
+    /// Report and skip the bad rows with `skip_bad_rows`, see [`Transaction::skip_bad_row`].
+    /// Without the option the errors are still yielded, so the caller can stop at the first one.
+    pub fn skip_bad_rows(self) -> impl Iterator<Item = Result<Transaction, TransactionError>> + 'a {
+        let config: &Config = self.config;
+        self.filter_map(move |result| match result {
+            Ok(transaction) => Some(Ok(transaction)),
+            Err(e) => Transaction::skip_bad_row(e, config).err().map(Err),
+        })
+    }
+}
+
+impl Iterator for TransactionRecords<'_> {
+    type Item = Result<Transaction, TransactionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record: Result<csv::StringRecord, TransactionError> = self.records.next()?;
+        Some(record.and_then(|sr| self.refs.read(&sr, &self.columns, self.config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixed_width::FixedWidthSpec;
+    use super::*;
+
+    #[test]
+    fn transaction_records_test() {
+        let tx: &str =
+            "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, 1, x, 1.0\nwithdrawal, 1, 3, 0.5\n";
+        let mut refs: DepositRefs = DepositRefs::default();
+        let config: Config = Config::default();
+
+        let results: Vec<Result<Transaction, TransactionError>> =
+            TransactionRecords::new(tx.as_bytes(), &mut refs, &config)
+                .unwrap()
+                .collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().tx, 1);
+        assert_eq!(
+            results[1],
+            Err(TransactionError::InvalidTx {
+                line: 3,
+                value: "x".to_string(),
+            })
+        );
+        assert_eq!(results[2].as_ref().unwrap().tx, 3);
+
+        //Strict, the error is still yielded
+        let mut refs: DepositRefs = DepositRefs::default();
+        let results: Vec<Result<Transaction, TransactionError>> =
+            TransactionRecords::new(tx.as_bytes(), &mut refs, &config)
+                .unwrap()
+                .skip_bad_rows()
+                .collect();
+        assert!(results[1].is_err());
+
+        //Lenient, the bad row is skipped
+        let config: Config = Config {
+            skip_bad_rows: true,
+            ..Config::default()
+        };
+        let mut refs: DepositRefs = DepositRefs::default();
+        let txs: Vec<u32> = TransactionRecords::new(tx.as_bytes(), &mut refs, &config)
+            .unwrap()
+            .skip_bad_rows()
+            .map(|result| result.unwrap().tx)
+            .collect();
+        assert_eq!(txs, [1, 3]);
+    }
+
+    #[test]
+    fn fixed_width_records_test() {
+        let config: Config = Config {
+            fixed_width: Some(
+                FixedWidthSpec::parse("type=0-10,client=10-15,tx=15-20,amount=20-30").unwrap(),
+            ),
+            ..Config::default()
+        };
+        let tx: &str = "deposit       1    1       2.0\n\nwithdrawal    1    y       1.0\n";
+        let mut refs: DepositRefs = DepositRefs::default();
+
+        let records: TransactionRecords =
+            TransactionRecords::new(tx.as_bytes(), &mut refs, &config).unwrap();
+        assert_eq!(records.columns(), &Columns::default());
+
+        let results: Vec<Result<Transaction, TransactionError>> = records.collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(TransactionError::InvalidTx { line: 3, .. })
+        ));
+    }
+}
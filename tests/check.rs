@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_accounts-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn check_test() {
+    let input: PathBuf = std::env::temp_dir().join("accounts-cli-check-input.csv");
+    fs::write(
+        &input,
+        "type, client, tx, amount\n\
+         deposit, 1, 1, 2.0\n\
+         deposit, one, 2, 1.0\n\
+         withdrawal, 1, 3, 1.0\n\
+         deposit, 1, 4, -1.0\n\
+         refund, 1, 5, 1.0\n\
+         deposit, 2, 1, 1.0\n\
+         dispute, 1, 1\n",
+    )
+    .unwrap();
+
+    //Every error is listed with its line and no client is written
+    let result: Output = run(&[input.to_str().unwrap(), "--check"]);
+    assert_eq!(result.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "Valid rows 3\n\
+         Errors 4\n\
+         Line 3: invalid client ID \"one\"\n\
+         Line 5: tx 4 has a negative amount\n\
+         Line 6: invalid transaction type \"refund\"\n\
+         Line 7: tx ID 1 is used by another transaction\n"
+    );
+
+    let valid: PathBuf = std::env::temp_dir().join("accounts-cli-check-valid.csv");
+    fs::write(&valid, "type, client, tx, amount\ndeposit, 1, 1, 2.0\n").unwrap();
+    let result: Output = run(&[valid.to_str().unwrap(), "--check", "--stream"]);
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "Valid rows 1\nErrors 0\n"
+    );
+
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&valid).unwrap();
+}
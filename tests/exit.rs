@@ -0,0 +1,67 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_accounts-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn exit_code_test() {
+    let input: PathBuf = std::env::temp_dir().join("accounts-cli-exit-input.csv");
+    fs::write(&input, "type, client, tx, amount\ndeposit, one, 1, 1.0\n").unwrap();
+    let missing: PathBuf = std::env::temp_dir().join("accounts-cli-exit-missing.csv");
+    let unwritable: PathBuf = std::env::temp_dir().join("accounts-cli-missing-dir/accounts.csv");
+    let input: &str = input.to_str().unwrap();
+
+    //A bad row
+    assert_eq!(run(&[input]).status.code(), Some(1));
+    assert_eq!(run(&[input, "--stream"]).status.code(), Some(1));
+    //An unknown option
+    assert_eq!(run(&[input, "--unknown"]).status.code(), Some(2));
+    //A path that doesn't exist
+    assert_eq!(run(&[missing.to_str().unwrap()]).status.code(), Some(3));
+    //A path that can't be read, a directory
+    let directory: PathBuf = std::env::temp_dir();
+    assert_eq!(run(&[directory.to_str().unwrap()]).status.code(), Some(4));
+    assert_eq!(
+        run(&[directory.to_str().unwrap(), "--check"]).status.code(),
+        Some(4)
+    );
+
+    //A corrupt gzip file can't be read, even when the bad rows are skipped
+    let gzip: PathBuf = std::env::temp_dir().join("accounts-cli-exit-corrupt.csv.gz");
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(b"type, client, tx, amount\ndeposit, 1, 1, 1.0\n")
+        .unwrap();
+    let mut compressed: Vec<u8> = encoder.finish().unwrap();
+    let checksum: usize = compressed.len() - 8;
+    compressed[checksum] ^= 0xff;
+    fs::write(&gzip, compressed).unwrap();
+    for stream in [false, true] {
+        let mut args: Vec<&str> = vec![gzip.to_str().unwrap(), "--skip-bad-rows"];
+        if stream {
+            args.push("--stream");
+        }
+        assert_eq!(run(&args).status.code(), Some(4));
+    }
+    fs::remove_file(&gzip).unwrap();
+
+    //An output that can't be written
+    fs::write(input, "type, client, tx, amount\ndeposit, 1, 1, 1.0\n").unwrap();
+    for stream in [false, true] {
+        let mut args: Vec<&str> = vec![input, "--output", unwritable.to_str().unwrap()];
+        if stream {
+            args.push("--stream");
+        }
+        assert_eq!(run(&args).status.code(), Some(5));
+    }
+    assert!(run(&[input]).status.success());
+
+    fs::remove_file(input).unwrap();
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_accounts-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn files_test() {
+    let first: PathBuf = std::env::temp_dir().join("accounts-cli-files-first.csv");
+    let second: PathBuf = std::env::temp_dir().join("accounts-cli-files-second.csv");
+    fs::write(
+        &first,
+        "type, client, tx, amount\n\
+         deposit, 1, 1, 10.0\n\
+         withdrawal, 1, 4, 2.5\n",
+    )
+    .unwrap();
+    //Tx 4 repeats the withdrawal of the first file
+    fs::write(
+        &second,
+        "type, client, tx, amount\n\
+         deposit, 1, 2, 4.0\n\
+         deposit, 1, 4, 100.0\n\
+         dispute, 1, 2\n",
+    )
+    .unwrap();
+    let paths: [&str; 2] = [first.to_str().unwrap(), second.to_str().unwrap()];
+
+    //The files are applied as one input, the repeated tx ID is dropped
+    for order in ["tx", "file"] {
+        let result: Output = run(&[paths[0], paths[1], "--process-order", order]);
+        assert!(result.status.success());
+        assert_eq!(
+            String::from_utf8(result.stdout).unwrap(),
+            "client,available,held,total,locked\n1,7.5000,4.0000,11.5000,false\n"
+        );
+        assert_eq!(
+            String::from_utf8(result.stderr).unwrap(),
+            "Skipping Line 3: tx ID 4 is used by another transaction\n"
+        );
+    }
+
+    let result: Output = run(&[paths[0], paths[1], "--strict-tx-ids"]);
+    assert_eq!(result.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "Line 3: tx ID 4 is used by another transaction\n"
+    );
+
+    fs::remove_file(&first).unwrap();
+    fs::remove_file(&second).unwrap();
+}
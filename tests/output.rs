@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_accounts-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn output_test() {
+    let input: PathBuf = std::env::temp_dir().join("accounts-cli-output-input.csv");
+    let output: PathBuf = std::env::temp_dir().join("accounts-cli-output.csv");
+    fs::write(&input, "type, client, tx, amount\ndeposit, 1, 1, 2.0\n").unwrap();
+    let expected: &str = "client,available,held,total,locked\n1,2.0000,0.0000,2.0000,false\n";
+
+    //The clients go to the file and nothing to stdout, with and without --stream
+    for stream in [&[][..], &["--stream"][..]] {
+        let _ = fs::remove_file(&output);
+        let mut args: Vec<&str> = vec![
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+        ];
+        args.extend(stream);
+
+        let result: Output = run(&args);
+        assert!(result.status.success());
+        assert!(result.stdout.is_empty());
+        assert_eq!(fs::read_to_string(&output).unwrap(), expected);
+    }
+    fs::remove_file(&output).unwrap();
+
+    //A path that can't be written is an error
+    let missing: PathBuf = std::env::temp_dir().join("accounts-cli-missing-dir/accounts.csv");
+    let result: Output = run(&[
+        input.to_str().unwrap(),
+        "--output",
+        missing.to_str().unwrap(),
+    ]);
+    assert_eq!(result.status.code(), Some(5));
+    assert!(String::from_utf8(result.stdout)
+        .unwrap()
+        .starts_with("Something went wrong writing the output"));
+    fs::remove_file(&input).unwrap();
+}
@@ -0,0 +1,31 @@
+use std::io::Write;
+use std::process::{Child, Command, Output, Stdio};
+
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child: Child = Command::new(env!("CARGO_BIN_EXE_accounts-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn stdin_test() {
+    let input: &str = "type, client, tx, amount\ndeposit, 1, 1, 2.0\nwithdrawal, 1, 2, 0.5\n";
+    let expected: &str = "client,available,held,total,locked\n1,1.5000,0.0000,1.5000,false\n";
+
+    //Without a path and with `-` the CSV is read from stdin
+    for args in [&[][..], &["-"][..]] {
+        let output: Output = run_with_stdin(args, input);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}